@@ -0,0 +1,10 @@
+use pop_launcher_plugins::{host::Host, manifest, plugins, run_plugin};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    run_plugin(manifest::HOST.id, || {
+        // Without a prefix, commando gets every query the others do not claim
+        Ok(manifest::PLUGINS.iter().fold(Host::new(), plugins::start))
+    })
+    .await;
+}
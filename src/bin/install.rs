@@ -0,0 +1,163 @@
+use std::{
+    env,
+    fs::{self, create_dir_all, remove_dir, remove_file, write},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use anyhow::{anyhow, Context};
+use pop_launcher_plugins::{
+    manifest::{self, Manifest},
+    QueryPrefix,
+};
+
+const USAGE: &str = "\
+Usage: install [--uninstall] [--copy] [--bin-dir DIR] [PLUGIN...]
+
+Installs the plugins (all of them if none are given) into
+$XDG_DATA_HOME/pop-launcher/plugins/<plugin>/, writing plugin.ron and linking the binary.
+Install `host` on its own to run all plugins in a single process instead. Its queries are not
+isolated from other plugins' results, and the launcher sorts them.
+
+  --uninstall    Remove the plugin.ron and binary again, leaving config files alone
+  --copy         Copy the binaries instead of symlinking them
+  --bin-dir DIR  Where the built binaries are, defaults to the directory of this binary";
+
+struct Options {
+    uninstall: bool,
+    copy: bool,
+    bin_dir: Option<PathBuf>,
+    plugins: Vec<&'static Manifest>,
+}
+
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("Error: {error:#}");
+        exit(1);
+    }
+}
+
+fn run() -> anyhow::Result<()> {
+    let options = parse_args(env::args().skip(1))?;
+
+    let xdg = xdg::BaseDirectories::with_prefix("pop-launcher")?;
+    let plugins_dir = xdg.get_data_home().join("plugins");
+
+    let bin_dir = match options.bin_dir {
+        Some(bin_dir) => bin_dir,
+        None => env::current_exe()?
+            .parent()
+            .ok_or_else(|| anyhow!("Could not determine binary directory"))?
+            .to_owned(),
+    };
+
+    for manifest in options.plugins {
+        let plugin_dir = plugins_dir.join(manifest.id);
+
+        if options.uninstall {
+            uninstall(manifest, &plugin_dir)?;
+            println!("Uninstalled {} from {}", manifest.id, plugin_dir.display());
+        } else {
+            install(manifest, &bin_dir, &plugin_dir, options.copy)?;
+            println!("Installed {} to {}", manifest.id, plugin_dir.display());
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Options> {
+    let mut options = Options {
+        uninstall: false,
+        copy: false,
+        bin_dir: None,
+        plugins: Vec::new(),
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--uninstall" => options.uninstall = true,
+            "--copy" => options.copy = true,
+            "--bin-dir" => {
+                let dir = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--bin-dir needs a value"))?;
+                options.bin_dir = Some(PathBuf::from(dir));
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                exit(0);
+            }
+            id => {
+                let manifest =
+                    manifest::find(id).ok_or_else(|| anyhow!("Unknown plugin {id}\n\n{USAGE}"))?;
+                options.plugins.push(manifest);
+            }
+        }
+    }
+
+    if options.plugins.is_empty() {
+        options.plugins = manifest::PLUGINS.iter().collect();
+    }
+
+    Ok(options)
+}
+
+fn install(
+    manifest: &Manifest,
+    bin_dir: &Path,
+    plugin_dir: &Path,
+    copy: bool,
+) -> anyhow::Result<()> {
+    let binary = bin_dir.join(manifest.id);
+    if !binary.is_file() {
+        return Err(anyhow!(
+            "{} does not exist, build the plugins first",
+            binary.display()
+        ));
+    }
+
+    create_dir_all(plugin_dir)
+        .with_context(|| format!("Could not create {}", plugin_dir.display()))?;
+
+    let prefix = QueryPrefix::load(manifest);
+    write(
+        plugin_dir.join("plugin.ron"),
+        manifest.to_ron(prefix.as_ref())? + "\n",
+    )
+    .with_context(|| format!("Could not write plugin.ron for {}", manifest.id))?;
+
+    let target = plugin_dir.join(manifest.id);
+    remove_if_exists(&target)?;
+
+    if copy {
+        fs::copy(&binary, &target)
+            .with_context(|| format!("Could not copy {}", binary.display()))?;
+    } else {
+        let binary = binary.canonicalize()?;
+        std::os::unix::fs::symlink(&binary, &target)
+            .with_context(|| format!("Could not link {}", binary.display()))?;
+    }
+
+    Ok(())
+}
+
+fn uninstall(manifest: &Manifest, plugin_dir: &Path) -> anyhow::Result<()> {
+    remove_if_exists(&plugin_dir.join("plugin.ron"))?;
+    remove_if_exists(&plugin_dir.join(manifest.id))?;
+
+    // Only succeeds if the user did not leave a config file or anything else behind
+    let _ = remove_dir(plugin_dir);
+
+    Ok(())
+}
+
+fn remove_if_exists(path: &Path) -> anyhow::Result<()> {
+    match remove_file(path) {
+        Err(error) if error.kind() != ErrorKind::NotFound => {
+            Err(error).with_context(|| format!("Could not remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
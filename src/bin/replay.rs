@@ -0,0 +1,177 @@
+use std::{env, path::PathBuf, process::exit, time::Duration};
+
+use futures_lite::stream;
+use pop_launcher::Request;
+use pop_launcher_plugins::{
+    cli::MemoryOutput,
+    clipboard,
+    host::Host,
+    logging, manifest, notify, plugins,
+    record::{self, Event},
+    serve, start_plugin, PopLauncherPlugin,
+};
+use tokio::time::{sleep_until, Instant};
+
+const USAGE: &str = "\
+Usage: replay [--fast] PLUGIN RECORDING
+
+Feeds the requests of a recording made with `record_file` into the plugin again and shows how
+its responses differ from the recorded ones. Activations are carried out again.
+
+  --fast  Send the requests right away instead of as far apart as they were recorded. Newer
+          searches then interrupt older ones that ran to completion in the recording";
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let mut fast = false;
+    let mut positional = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--fast" => fast = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                exit(0);
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let (manifest, path) = match positional.as_slice() {
+        [id, path] => match manifest::find(id) {
+            Some(manifest) => (manifest, PathBuf::from(path)),
+            None => fail(&format!("Unknown plugin {id}\n\n{USAGE}")),
+        },
+        _ => fail(USAGE),
+    };
+
+    let entries = match record::read(&path) {
+        Ok(entries) => entries,
+        Err(error) => fail(&format!("Could not read {}: {error}", path.display())),
+    };
+
+    logging::init_stderr(manifest.id);
+    notify::init(manifest.id);
+    clipboard::init(manifest.id);
+
+    // Like the plugin's own binary would, so the responses go through the same responder
+    let mut plugin: Box<dyn PopLauncherPlugin> = match manifest.id {
+        "host" => start_plugin(manifest.id, || {
+            Ok(manifest::PLUGINS.iter().fold(Host::new(), plugins::start))
+        }),
+        _ => match plugins::init(manifest) {
+            Some(plugin) => plugin,
+            None => fail(&format!(
+                "The {} plugin is not part of this build",
+                manifest.id
+            )),
+        },
+    };
+    let output = MemoryOutput::default();
+    plugin.responder().set_output(output.clone());
+
+    let mut requests = Vec::new();
+    let mut expected = Vec::new();
+    for entry in entries {
+        match entry.event {
+            Event::Request(request) => requests.push((entry.time, request)),
+            Event::Response(response) => expected.push(to_line(&response)),
+        }
+    }
+    let request_count = requests.len();
+
+    let start = Instant::now();
+    let requests = stream::unfold(requests.into_iter(), move |mut requests| async move {
+        let (time, request) = requests.next()?;
+        if !fast {
+            sleep_until(start + Duration::from_millis(time)).await;
+        }
+        Some((Ok::<Request, serde_json::Error>(request), requests))
+    });
+    serve(plugin.as_mut(), Box::pin(requests)).await;
+    notify::shutdown();
+
+    let actual: Vec<String> = output.take_responses().iter().map(to_line).collect();
+    if actual == expected {
+        println!("Replayed {request_count} requests, the responses match the recording");
+        return;
+    }
+
+    for line in diff(&expected, &actual) {
+        println!("{line}");
+    }
+    println!("Replayed {request_count} requests, the responses differ from the recording");
+    exit(1);
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{message}");
+    exit(2);
+}
+
+fn to_line<T: serde::Serialize>(response: &T) -> String {
+    serde_json::to_string(response).unwrap_or_default()
+}
+
+// Line diff of the longest common subsequence: `-` only recorded, `+` only replayed
+fn diff(expected: &[String], actual: &[String]) -> Vec<String> {
+    let mut common = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push(format!("  {}", expected[i]));
+            i += 1;
+            j += 1;
+        } else if j == actual.len() || (i < expected.len() && common[i + 1][j] >= common[i][j + 1])
+        {
+            lines.push(format!("- {}", expected[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", actual[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn identical_responses_have_no_changes() {
+        let responses = lines(&["Clear", "Finished"]);
+        assert_eq!(diff(&responses, &responses), ["  Clear", "  Finished"]);
+    }
+
+    #[test]
+    fn changes_keep_the_common_lines() {
+        let expected = lines(&["Clear", "a", "b", "Finished"]);
+        let actual = lines(&["Clear", "b", "c", "Finished"]);
+        assert_eq!(
+            diff(&expected, &actual),
+            ["  Clear", "- a", "  b", "+ c", "  Finished"]
+        );
+    }
+
+    #[test]
+    fn removals_come_before_additions() {
+        assert_eq!(diff(&lines(&["a"]), &lines(&["b"])), ["- a", "+ b"]);
+        assert_eq!(diff(&lines(&[]), &lines(&["b"])), ["+ b"]);
+        assert_eq!(diff(&lines(&["a"]), &lines(&[])), ["- a"]);
+    }
+}
@@ -0,0 +1,293 @@
+//! Plugin indexes kept on disk between runs, so a plugin can answer right away after starting.
+
+use std::{
+    fs::{read, write},
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use blocking::{unblock, Task};
+use futures_lite::future;
+use log::{info, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    watch::{fingerprint, Fingerprint},
+    Result,
+};
+
+#[derive(Serialize, Deserialize)]
+struct Stored<T> {
+    sources: Vec<PathBuf>,
+    basis: Basis,
+    value: T,
+}
+
+// What an index was built from: the state of its sources and when that was
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+struct Basis {
+    fingerprints: Vec<Fingerprint>,
+    time: SystemTime,
+}
+
+impl Basis {
+    fn now(sources: &[PathBuf]) -> Self {
+        Self {
+            fingerprints: sources.iter().map(|path| fingerprint(path)).collect(),
+            time: SystemTime::now(),
+        }
+    }
+}
+
+/// An index built from some source paths, stored in
+/// `$XDG_CACHE_HOME/pop-launcher/plugins/<plugin>/<name>.json`.
+///
+/// The stored index is used right away, even if it is stale. [`Cache::refresh`] notices when
+/// the sources changed (see [`crate::watch::Watcher`] for what counts as a change) or the index
+/// got older than its maximum age, and rebuilds it on a background thread in the meantime.
+pub struct Cache<T> {
+    name: String,
+    file: Option<PathBuf>,
+    sources: Vec<PathBuf>,
+    // Whether changes to the sources make the index stale, not just its age
+    fingerprinted: bool,
+    max_age: Option<Duration>,
+    value: Option<T>,
+    // Basis of the latest build, failed ones included so they are not retried on every call
+    basis: Option<Basis>,
+    rebuild: Option<Task<(Basis, Result<T>)>>,
+}
+
+impl<T> Cache<T>
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+{
+    /// Loads the stored index. It is ignored if it was built from other sources.
+    pub fn load(plugin_name: &str, name: &str, sources: Vec<PathBuf>) -> Self {
+        let file = cache_file(plugin_name, name)
+            .map_err(|error| warn!("Could not determine cache location for {name}: {error}"))
+            .ok();
+
+        let stored = file
+            .as_ref()
+            .and_then(|file| read(file).ok())
+            .and_then(|data| match serde_json::from_slice::<Stored<T>>(&data) {
+                Ok(stored) => Some(stored),
+                Err(error) => {
+                    warn!("Could not parse cached {name}, rebuilding it: {error}");
+                    None
+                }
+            })
+            .filter(|stored| stored.sources == sources);
+
+        let (value, basis) = match stored {
+            Some(stored) => (Some(stored.value), Some(stored.basis)),
+            None => (None, None),
+        };
+
+        Self {
+            name: name.to_owned(),
+            file,
+            sources,
+            fingerprinted: true,
+            max_age: None,
+            value,
+            basis,
+            rebuild: None,
+        }
+    }
+
+    /// Rebuilds the index once it is older than `max_age`, even if the sources look unchanged.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Leaves rebuilding to the maximum age and [`Cache::invalidate`], for sources like a whole
+    /// home directory, whose top level changes all the time while what is indexed lies deeper.
+    /// The sources then only tell indexes apart.
+    pub fn ignore_changes(mut self) -> Self {
+        self.fingerprinted = false;
+        self
+    }
+
+    /// Rebuilds the index with the next [`Cache::refresh`], whatever its sources and age.
+    pub fn invalidate(&mut self) {
+        self.basis = None;
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    /// Builds the index right here if there is none yet, so the caller can fail if that fails.
+    pub fn ensure<F>(&mut self, build: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<T>,
+    {
+        if self.value.is_none() {
+            let basis = Basis::now(&self.sources);
+            let value = build()?;
+            self.store(basis, value);
+        }
+        Ok(())
+    }
+
+    /// Picks up a finished rebuild, or starts one if the index is stale. Never waits for it.
+    pub async fn refresh<F>(&mut self, build: F)
+    where
+        F: FnOnce() -> Result<T> + Send + 'static,
+    {
+        if let Some(mut rebuild) = self.rebuild.take() {
+            match future::poll_once(&mut rebuild).await {
+                Some((basis, Ok(value))) => self.store(basis, value),
+                Some((basis, Err(error))) => {
+                    warn!("Could not rebuild {}: {error}", self.name);
+                    self.basis = Some(basis);
+                }
+                None => self.rebuild = Some(rebuild),
+            }
+            return;
+        }
+
+        if !self.is_stale() {
+            return;
+        }
+
+        info!("Rebuilding {} in the background", self.name);
+        let sources = self.sources.clone();
+        self.rebuild = Some(unblock(move || {
+            // Taken before building, so changes made meanwhile trigger another rebuild
+            let basis = Basis::now(&sources);
+            (basis, build())
+        }));
+    }
+
+    fn is_stale(&self) -> bool {
+        let basis = match &self.basis {
+            Some(basis) => basis,
+            None => return true,
+        };
+
+        let expired = self
+            .max_age
+            .is_some_and(|max_age| basis.time.elapsed().map_or(true, |age| age > max_age));
+
+        expired
+            || (self.fingerprinted && basis.fingerprints != Basis::now(&self.sources).fingerprints)
+    }
+
+    fn store(&mut self, basis: Basis, value: T) {
+        let stored = Stored {
+            sources: self.sources.clone(),
+            basis: basis.clone(),
+            value,
+        };
+
+        if let Some(file) = &self.file {
+            let result = serde_json::to_vec(&stored)
+                .map_err(std::io::Error::from)
+                .and_then(|data| write(file, data));
+            if let Err(error) = result {
+                warn!("Could not write cache {file:?}: {error}");
+            }
+        }
+
+        self.value = Some(stored.value);
+        self.basis = Some(basis);
+    }
+}
+
+fn cache_file(plugin_name: &str, name: &str) -> anyhow::Result<PathBuf> {
+    let xdg = xdg::BaseDirectories::with_prefix("pop-launcher")?;
+    Ok(xdg.place_cache_file(
+        PathBuf::from("plugins")
+            .join(plugin_name)
+            .join(format!("{name}.json")),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::testing::XdgHome;
+
+    fn load(source: &Path) -> Cache<Vec<String>> {
+        Cache::load("test", "index", vec![source.to_owned()])
+    }
+
+    fn index(value: &str) -> Result<Vec<String>> {
+        Ok(vec![value.to_owned()])
+    }
+
+    // Refreshes, building `value` if the index is stale, and waits for the rebuild to be picked up
+    async fn settle(cache: &mut Cache<Vec<String>>, value: &'static str) -> String {
+        cache.refresh(move || index(value)).await;
+        while cache.rebuild.is_some() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            cache
+                .refresh(|| unreachable!("Only one rebuild at a time"))
+                .await;
+        }
+        cache.get().expect("No index")[0].clone()
+    }
+
+    #[tokio::test]
+    async fn stored_indexes_are_kept_for_the_same_sources() {
+        let home = XdgHome::new();
+        let first = home.write("first/file", "");
+        let second = home.write("second/file", "");
+
+        load(first.parent().unwrap()).ensure(|| index("a")).unwrap();
+
+        let mut cache = load(first.parent().unwrap());
+        cache.ensure(|| unreachable!("Loaded from disk")).unwrap();
+        assert_eq!(settle(&mut cache, "b").await, "a");
+        assert!(load(second.parent().unwrap()).get().is_none());
+    }
+
+    #[tokio::test]
+    async fn changed_sources_are_rebuilt_in_the_background() {
+        let home = XdgHome::new();
+        let source = home.write("source/file", "");
+        let source = source.parent().unwrap();
+
+        let mut cache = load(source);
+        cache.ensure(|| index("a")).unwrap();
+        home.write("source/other", "");
+        assert_eq!(settle(&mut cache, "b").await, "b");
+        assert_eq!(settle(&mut cache, "c").await, "b");
+
+        // Stored along with the state it was built from
+        assert_eq!(settle(&mut load(source), "c").await, "b");
+    }
+
+    #[tokio::test]
+    async fn old_indexes_are_rebuilt() {
+        let home = XdgHome::new();
+        let source = home.write("source/file", "");
+
+        let mut cache = load(source.parent().unwrap()).max_age(Duration::from_millis(1));
+        cache.ensure(|| index("a")).unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(settle(&mut cache, "b").await, "b");
+    }
+
+    #[tokio::test]
+    async fn ignoring_changes_leaves_rebuilds_to_invalidation() {
+        let home = XdgHome::new();
+        let source = home.write("source/file", "");
+
+        let mut cache = load(source.parent().unwrap())
+            .max_age(Duration::from_secs(60))
+            .ignore_changes();
+        cache.ensure(|| index("a")).unwrap();
+        home.write("source/other", "");
+        assert_eq!(settle(&mut cache, "b").await, "a");
+
+        cache.invalidate();
+        assert_eq!(settle(&mut cache, "c").await, "c");
+    }
+}
@@ -0,0 +1,236 @@
+//! Running a plugin from a terminal: one query, optionally followed by an activation, with the
+//! responses printed instead of sent to a launcher.
+
+use std::{
+    cell::RefCell,
+    io,
+    pin::Pin,
+    process::exit,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use futures_lite::AsyncWrite;
+use log::warn;
+use pop_launcher::{PluginResponse, PluginSearchResult, Request};
+
+use crate::{activate_notice, PopLauncherPlugin, FAILURE_ID};
+
+pub const USAGE: &str = "\
+Options, all of them for running the plugin from a terminal instead of the launcher:
+
+  --query QUERY  Search for QUERY, including the plugin's prefix, e.g. --query \"kicad amp\"
+  --activate N   Activate result N of the query afterwards
+  --complete N   Complete result N of the query afterwards
+  --json         Print the responses as JSON lines like the launcher receives them";
+
+/// What to do instead of serving the launcher.
+#[derive(Debug, Default)]
+pub struct Options {
+    query: String,
+    then: Option<Then>,
+    json: bool,
+}
+
+#[derive(Debug)]
+enum Then {
+    Activate(usize),
+    Complete(usize),
+}
+
+impl Options {
+    /// Parses the command line arguments. `None` if there are none, so the launcher is served.
+    /// `-h` and `--help` print [`USAGE`] and exit.
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut options = Options::default();
+        let mut query = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--query" => query = Some(args.next().ok_or("--query needs a value")?),
+                "--activate" => options.then = Some(Then::Activate(index(args.next())?)),
+                "--complete" => options.then = Some(Then::Complete(index(args.next())?)),
+                "--json" => options.json = true,
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    exit(0);
+                }
+                arg => return Err(format!("Unknown argument {arg}\n\n{USAGE}")),
+            }
+        }
+
+        match query {
+            Some(query) => {
+                options.query = query;
+                Ok(Some(options))
+            }
+            None if options.then.is_some() || options.json => {
+                Err(format!("--query is missing\n\n{USAGE}"))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+fn index(value: Option<String>) -> Result<usize, String> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| String::from("--activate and --complete need a result number"))
+}
+
+/// An in-memory writer whose clones all append to the same buffer.
+#[derive(Clone, Default)]
+pub struct MemoryOutput {
+    buffer: Rc<RefCell<Vec<u8>>>,
+}
+
+impl MemoryOutput {
+    /// Decodes every complete JSON line written so far and empties the buffer.
+    pub fn take_responses(&self) -> Vec<PluginResponse> {
+        let data = std::mem::take(&mut *self.buffer.borrow_mut());
+
+        String::from_utf8_lossy(&data)
+            .lines()
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(response) => Some(response),
+                Err(error) => {
+                    warn!("Plugin wrote invalid response {line:?}: {error}");
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+impl AsyncWrite for MemoryOutput {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.buffer.borrow_mut().extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Runs the query against the plugin and prints what it answers.
+pub async fn run<P: PopLauncherPlugin>(plugin: &mut P, options: Options) {
+    let output = MemoryOutput::default();
+    plugin.responder().set_output(output.clone());
+
+    plugin.request(Request::Search(options.query.clone())).await;
+    let responses = output.take_responses();
+    print(&responses, options.json);
+
+    let then = match options.then {
+        Some(then) => then,
+        None => return finish(plugin).await,
+    };
+
+    let results = result_ids(&responses);
+    let number = match then {
+        Then::Activate(number) | Then::Complete(number) => number,
+    };
+    let id = match results.get(number) {
+        Some(&id) => id,
+        None => {
+            eprintln!(
+                "There is no result {number}, the query had {}",
+                results.len()
+            );
+            return finish(plugin).await;
+        }
+    };
+
+    match then {
+        Then::Activate(_) if plugin.responder().is_more_results(id) => {
+            plugin.responder().raise_limit();
+            plugin.request(Request::Search(options.query)).await;
+        }
+        Then::Activate(_) if plugin.responder().notice(id).is_some() => {
+            activate_notice(plugin.responder(), id).await
+        }
+        Then::Activate(_) if id == FAILURE_ID => println!("Close"),
+        Then::Activate(_) => plugin.request(Request::Activate(id)).await,
+        Then::Complete(_) => plugin.request(Request::Complete(id)).await,
+    }
+    print(&output.take_responses(), options.json);
+
+    finish(plugin).await;
+}
+
+// The ids of the results as the launcher would list them after these responses
+fn result_ids(responses: &[PluginResponse]) -> Vec<u32> {
+    let mut ids = Vec::new();
+    for response in responses {
+        match response {
+            PluginResponse::Append(result) => ids.push(result.id),
+            PluginResponse::Clear => ids.clear(),
+            _ => (),
+        }
+    }
+    ids
+}
+
+async fn finish<P: PopLauncherPlugin>(plugin: &mut P) {
+    plugin.request(Request::Exit).await;
+    plugin.shutdown().await;
+}
+
+fn print(responses: &[PluginResponse], json: bool) {
+    if json {
+        for response in responses {
+            if let Ok(line) = serde_json::to_string(response) {
+                println!("{line}");
+            }
+        }
+        return;
+    }
+
+    let mut results = Vec::new();
+    for response in responses {
+        match response {
+            PluginResponse::Append(result) => results.push(result),
+            PluginResponse::Clear => results.clear(),
+            PluginResponse::Finished => print_table(&results),
+            PluginResponse::Fill(text) => println!("Fill: {text}"),
+            PluginResponse::Close => println!("Close"),
+            other => println!("{}", serde_json::to_string(other).unwrap_or_default()),
+        }
+    }
+}
+
+fn print_table(results: &[&PluginSearchResult]) {
+    if results.is_empty() {
+        println!("No results");
+        return;
+    }
+
+    let name_width = results
+        .iter()
+        .map(|result| result.name.chars().count())
+        .max()
+        .unwrap_or_default()
+        .max("NAME".len());
+
+    println!(
+        "{:>3}  {:>10}  {:name_width$}  DESCRIPTION",
+        "#", "ID", "NAME"
+    );
+    for (number, result) in results.iter().enumerate() {
+        println!(
+            "{number:>3}  {:>10}  {:name_width$}  {}",
+            result.id,
+            result.name,
+            result.description.replace('\n', " "),
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Copying text to the clipboard with `wl-copy`, `xclip` or `xsel`.
+//!
+//! The tool is picked by the kind of session, the first one installed wins. Set `clipboard` in
+//! the plugin's config to a command line reading the text from its input to use another one.
+
+use std::{cell::RefCell, env, io::Read, process::Stdio};
+
+use log::info;
+use merge::Merge;
+use serde::Deserialize;
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use crate::{launcher, load_config, notify, ContextAction, Error, Responder, Result};
+
+const WAYLAND: &[&[&str]] = &[&["wl-copy"]];
+const X11: &[&[&str]] = &[
+    &["xclip", "-selection", "clipboard"],
+    &["xsel", "--clipboard", "--input"],
+];
+
+thread_local! {
+    static CONFIGURED: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+#[derive(Deserialize, Merge, Default)]
+struct ClipboardConfig {
+    clipboard: Option<String>,
+}
+
+/// Reads the configured clipboard tool of the plugin. Called by [`crate::run_plugin`].
+pub fn init(plugin_name: &str) {
+    let (config, _) = load_config::<ClipboardConfig>(plugin_name);
+    CONFIGURED.with(|cell| *cell.borrow_mut() = config.clipboard);
+}
+
+/// The standard context action for copying something about a result, named "Copy `what`".
+pub fn copy_action<A>(what: &str, action: A) -> ContextAction<A> {
+    ContextAction::new(format!("Copy {what}"), action)
+}
+
+/// Puts `text` on the clipboard.
+pub async fn copy(text: &str) -> Result<()> {
+    if let Some(command_line) = CONFIGURED.with(|cell| cell.borrow().clone()) {
+        let mut words = shlex::split(&command_line).unwrap_or_default();
+        if words.is_empty() {
+            return Err(Error::InvalidCommand(command_line));
+        }
+        let program = words.remove(0);
+        return run((program, words), text).await;
+    }
+
+    let mut candidates = Vec::new();
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        candidates.extend_from_slice(WAYLAND);
+    }
+    if env::var_os("DISPLAY").is_some() {
+        candidates.extend_from_slice(X11);
+    }
+
+    for candidate in candidates {
+        let args = candidate[1..].iter().map(|arg| arg.to_string()).collect();
+        match run((candidate[0].to_owned(), args), text).await {
+            Err(Error::MissingTool(program)) => info!("{program} is not installed"),
+            outcome => return outcome,
+        }
+    }
+
+    Err(Error::unavailable(
+        "No clipboard tool found, install wl-copy, xclip or xsel",
+    ))
+}
+
+/// Copies `text` and closes the launcher, or shows why that did not work like
+/// [`launcher::finish`] does.
+pub async fn finish(responder: &mut Responder, text: &str) {
+    let outcome = copy(text).await;
+    if outcome.is_ok() {
+        notify::success("Copied to clipboard", text);
+    }
+    launcher::report(responder, outcome, "Could not copy").await;
+}
+
+async fn run((program, args): (String, Vec<String>), text: &str) -> Result<()> {
+    let (stderr, mut error_output) =
+        launcher::error_log().map_err(|error| Error::spawn(&program, error))?;
+
+    // The tools keep running in the background to hand out the text, possibly after we exit
+    let mut command = Command::new(&program);
+    command
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(stderr);
+    launcher::detach(&mut command);
+
+    info!("Copying with {program} {args:?}");
+    let mut child = command
+        .spawn()
+        .map_err(|error| Error::spawn(&program, error))?;
+
+    if let Some(mut input) = child.stdin.take() {
+        input
+            .write_all(text.as_bytes())
+            .await
+            .map_err(|error| Error::spawn(&program, error))?;
+    }
+
+    let status = child
+        .wait()
+        .await
+        .map_err(|error| Error::spawn(&program, error))?;
+    if status.success() {
+        return Ok(());
+    }
+
+    let mut stderr = Vec::new();
+    let _ = error_output.read_to_end(&mut stderr);
+    Err(Error::Launch {
+        stderr: String::from_utf8_lossy(&stderr).trim().to_owned(),
+        program,
+        status,
+    })
+}
@@ -0,0 +1,422 @@
+//! Loading plugin configuration, with diagnostics for files that could not be used.
+
+use std::{
+    any::type_name,
+    cell::RefCell,
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+    fs::read_to_string,
+    iter::once,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use merge::Merge;
+use serde::Deserialize;
+
+use crate::Notice;
+
+thread_local! {
+    // The diagnostics of the latest check of every config, by plugin and config type
+    static DIAGNOSTICS: RefCell<BTreeMap<(String, &'static str), Checked>> =
+        const { RefCell::new(BTreeMap::new()) };
+}
+
+struct Checked {
+    check: fn(&str) -> Vec<ConfigDiagnostic>,
+    diagnostics: Vec<ConfigDiagnostic>,
+}
+
+/// A configuration file that was skipped, and why.
+#[derive(Debug, Clone)]
+pub struct ConfigDiagnostic {
+    pub file: PathBuf,
+    /// Line and column, both starting at 1, if the problem could be pinpointed.
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl ConfigDiagnostic {
+    pub fn new(file: &Path, message: impl Display) -> Self {
+        Self {
+            file: file.to_owned(),
+            position: None,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn ron(file: &Path, error: ron::error::SpannedError) -> Self {
+        Self {
+            position: Some((error.position.line, error.position.col)),
+            ..Self::new(file, error.code)
+        }
+    }
+
+    pub fn toml(file: &Path, data: &str, error: toml::de::Error) -> Self {
+        Self {
+            position: error.span().map(|span| line_column(data, span.start)),
+            // Some errors, like a missing value, come without a message, only with the full
+            // report, whose first line is the part that fits a notice
+            ..match error.message() {
+                "" => Self::new(file, error.to_string().lines().next().unwrap_or_default()),
+                message => Self::new(file, message),
+            }
+        }
+    }
+
+    pub fn json(file: &Path, error: serde_json::Error) -> Self {
+        Self {
+            position: (error.line() > 0).then(|| (error.line(), error.column())),
+            ..Self::new(file, error)
+        }
+    }
+}
+
+impl Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some((line, column)) = self.position {
+            write!(f, ":{line}:{column}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl From<&ConfigDiagnostic> for Notice {
+    fn from(diagnostic: &ConfigDiagnostic) -> Self {
+        let file_name = diagnostic
+            .file
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+
+        let description = match diagnostic.position {
+            Some((line, column)) => format!("{line}:{column}: {}", diagnostic.message),
+            None => diagnostic.message.clone(),
+        };
+
+        Notice {
+            name: format!("Config error in {file_name}"),
+            description,
+            icon: Some(pop_launcher::IconSource::Name("dialog-error".into())),
+            open: Some(diagnostic.file.clone()),
+        }
+    }
+}
+
+/// File names a plugin config is read from, in order of precedence within a directory.
+const CONFIG_FILE_NAMES: &[&str] = &["config.ron", "config.toml", "config.json"];
+
+/// Loads the config of a plugin from all its config files, with `POP_LAUNCHER_<PLUGIN>_<KEY>`
+/// environment variables taking precedence over individual fields.
+///
+/// The diagnostics are also kept for [`diagnostics`], which the [`Responder`](crate::Responder)
+/// shows as notices, so callers are free to ignore them.
+pub fn load_config<Config>(plugin_name: &str) -> (Config, Vec<ConfigDiagnostic>)
+where
+    Config: Default + Merge + for<'a> Deserialize<'a>,
+{
+    let (config, diagnostics) = get_config(&existing_config_files(plugin_name));
+    let mut overrides: Config = get_env_overrides(plugin_name);
+    overrides.merge(config);
+
+    let key = (plugin_name.to_owned(), type_name::<Config>());
+    let others = DIAGNOSTICS.with(|all| {
+        let mut all = all.borrow_mut();
+        all.insert(
+            key.clone(),
+            Checked {
+                check: check::<Config>,
+                diagnostics: diagnostics.clone(),
+            },
+        );
+        all.iter()
+            .filter(|(other, _)| other.0 == plugin_name && **other != key)
+            .map(|(other, checked)| (other.clone(), checked.check))
+            .collect::<Vec<_>>()
+    });
+
+    // The other configs of the plugin read the same files, which may have changed since
+    for (other, check) in others {
+        let diagnostics = check(plugin_name);
+        DIAGNOSTICS.with(|all| {
+            if let Some(checked) = all.borrow_mut().get_mut(&other) {
+                checked.diagnostics = diagnostics;
+            }
+        });
+    }
+
+    (overrides, diagnostics)
+}
+
+fn existing_config_files(plugin_name: &str) -> Vec<PathBuf> {
+    get_config_files(plugin_name).unwrap_or_else(|error| {
+        warn!("Could not find config files for {plugin_name}: {error}");
+        Vec::new()
+    })
+}
+
+// The problems the plugin's files have for `Config` now, without logging them again
+fn check<Config>(plugin_name: &str) -> Vec<ConfigDiagnostic>
+where
+    Config: for<'a> Deserialize<'a>,
+{
+    existing_config_files(plugin_name)
+        .iter()
+        .filter_map(|path| parse_config_file::<Config>(path).err())
+        .collect()
+}
+
+/// The diagnostics of every config loaded with [`load_config`] on this thread, each problem once.
+/// Loading any config of a plugin checks its other configs again, so fixed files stop showing up
+/// even if only the plugin's own config is reloaded.
+pub fn diagnostics() -> Vec<ConfigDiagnostic> {
+    let mut seen = HashSet::new();
+    DIAGNOSTICS.with(|all| {
+        all.borrow()
+            .values()
+            .flat_map(|checked| &checked.diagnostics)
+            .filter(|diagnostic| seen.insert(diagnostic.to_string()))
+            .cloned()
+            .collect()
+    })
+}
+
+/// Merges all readable config files into one, picking the parser by file extension. Earlier
+/// files take precedence over later ones, matching the order of [`get_config_files`].
+///
+/// Files that could not be read or parsed are logged and returned as diagnostics instead of
+/// being silently ignored.
+pub fn get_config<Config>(files: &[PathBuf]) -> (Config, Vec<ConfigDiagnostic>)
+where
+    Config: Default + Merge + for<'a> Deserialize<'a>,
+{
+    let mut config = Config::default();
+    let mut diagnostics = Vec::new();
+
+    for path in files {
+        match parse_config_file(path) {
+            Ok(conf) => config.merge(conf),
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+
+    for diagnostic in &diagnostics {
+        warn!("Ignoring config file {diagnostic}");
+    }
+
+    (config, diagnostics)
+}
+
+fn parse_config_file<Config>(path: &Path) -> Result<Config, ConfigDiagnostic>
+where
+    Config: for<'a> Deserialize<'a>,
+{
+    let data = read_to_string(path).map_err(|error| ConfigDiagnostic::new(path, error))?;
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("ron") => ron::from_str(&data).map_err(|error| ConfigDiagnostic::ron(path, error)),
+        Some("toml") => {
+            toml::from_str(&data).map_err(|error| ConfigDiagnostic::toml(path, &data, error))
+        }
+        Some("json") => {
+            serde_json::from_str(&data).map_err(|error| ConfigDiagnostic::json(path, error))
+        }
+        _ => Err(ConfigDiagnostic::new(path, "Unsupported config format")),
+    }
+}
+
+/// Builds a config from `POP_LAUNCHER_<PLUGIN>_<KEY>` variables, one field at a time.
+///
+/// Values are read as JSON first, so numbers, booleans and lists work, and fall back to a plain
+/// string otherwise.
+fn get_env_overrides<Config>(plugin_name: &str) -> Config
+where
+    Config: Default + Merge + for<'a> Deserialize<'a>,
+{
+    let prefix = format!(
+        "POP_LAUNCHER_{}_",
+        plugin_name.to_uppercase().replace('-', "_")
+    );
+    let mut config = Config::default();
+
+    for (name, value) in std::env::vars() {
+        let key = match name.strip_prefix(&prefix) {
+            Some(key) if !key.is_empty() => key.to_lowercase(),
+            _ => continue,
+        };
+
+        let parsed = serde_json::from_str(&value).unwrap_or(serde_json::Value::Null);
+        let field = [parsed, serde_json::Value::String(value)]
+            .into_iter()
+            .filter(|value| !value.is_null())
+            .find_map(|value| {
+                let object =
+                    serde_json::Value::Object([(key.clone(), value)].into_iter().collect());
+                serde_json::from_value::<Config>(object).ok()
+            });
+
+        match field {
+            Some(field) => config.merge(field),
+            None => warn!("Ignoring {name}: not a valid value for {key}"),
+        }
+    }
+
+    config
+}
+
+/// All config files of a plugin that exist, most important first: the user's data directory
+/// before the system ones, and within a directory in the order of [`CONFIG_FILE_NAMES`].
+pub fn get_config_files(plugin_name: &str) -> anyhow::Result<Vec<PathBuf>> {
+    Ok(get_config_candidates(plugin_name)?
+        .into_iter()
+        .filter(|plugin_config| plugin_config.exists())
+        .collect())
+}
+
+/// Every path a config file of the plugin could live at, whether it exists or not.
+pub fn get_config_candidates(plugin_name: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let xdg = xdg::BaseDirectories::with_prefix("pop-launcher")?;
+    let home = xdg.get_data_home();
+    let dirs = xdg.get_data_dirs();
+
+    Ok(once(home)
+        .chain(dirs)
+        .flat_map(|dir| {
+            let plugin_dir = dir.join("plugins").join(plugin_name);
+            CONFIG_FILE_NAMES
+                .iter()
+                .map(move |file_name| plugin_dir.join(file_name))
+        })
+        .collect())
+}
+
+fn line_column(data: &str, offset: usize) -> (usize, usize) {
+    let before = data.get(..offset).unwrap_or(data);
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .map(|line| line.chars().count())
+        .unwrap_or_default()
+        + 1;
+
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::testing::{lock_env, TempDir};
+
+    #[derive(Deserialize, Merge, Default)]
+    struct Limits {
+        max_results: Option<usize>,
+    }
+
+    #[derive(Deserialize, Merge, Default)]
+    struct Prefix {
+        prefix: Option<String>,
+    }
+
+    #[derive(Deserialize, Merge, Default)]
+    struct Settings {
+        prefix: Option<String>,
+        max_results: Option<usize>,
+        aliases: Option<Vec<String>>,
+    }
+
+    #[test]
+    fn diagnostics_are_reported_once_until_fixed() {
+        let _env = lock_env();
+        let data = TempDir::new();
+        let config = data.write("pop-launcher/plugins/broken/config.toml", "prefix = ");
+        env::set_var("XDG_DATA_HOME", data.path());
+
+        load_config::<Limits>("broken");
+        load_config::<Prefix>("broken");
+        let reported = diagnostics();
+        assert_eq!(reported.len(), 1, "{reported:?}");
+        assert_eq!(reported[0].file, config);
+        assert_eq!(reported[0].position, Some((1, 10)));
+        assert_eq!(
+            Notice::from(&reported[0]).description,
+            "1:10: TOML parse error at line 1, column 10"
+        );
+
+        // Reloading either config is enough to notice the fix
+        data.write("pop-launcher/plugins/broken/config.toml", "prefix = \"b\"");
+        load_config::<Limits>("broken");
+        assert!(diagnostics().is_empty());
+        let (prefix, _) = load_config::<Prefix>("broken");
+        assert_eq!(prefix.prefix.as_deref(), Some("b"));
+
+        env::remove_var("XDG_DATA_HOME");
+    }
+
+    #[test]
+    fn diagnostics_for_one_config_survive_loading_another() {
+        let _env = lock_env();
+        let data = TempDir::new();
+        data.write(
+            "pop-launcher/plugins/typed/config.toml",
+            "max_results = \"many\"",
+        );
+        env::set_var("XDG_DATA_HOME", data.path());
+
+        load_config::<Limits>("typed");
+        let (_, own) = load_config::<Prefix>("typed");
+        assert!(own.is_empty());
+        let reported = diagnostics();
+        assert_eq!(reported.len(), 1, "{reported:?}");
+        assert!(
+            reported[0].message.contains("expected usize"),
+            "{reported:?}"
+        );
+
+        data.write("pop-launcher/plugins/typed/config.toml", "max_results = 3");
+        load_config::<Prefix>("typed");
+        assert!(diagnostics().is_empty());
+
+        env::remove_var("XDG_DATA_HOME");
+    }
+
+    #[test]
+    fn environment_overrides_single_fields() {
+        let _env = lock_env();
+        let data = TempDir::new();
+        data.write(
+            "pop-launcher/plugins/my-plugin/config.toml",
+            "prefix = \"file\"\nmax_results = 3",
+        );
+        env::set_var("XDG_DATA_HOME", data.path());
+        env::set_var("POP_LAUNCHER_MY_PLUGIN_MAX_RESULTS", "7");
+        env::set_var("POP_LAUNCHER_MY_PLUGIN_ALIASES", "[\"a\", \"b\"]");
+        // Not JSON, so taken as a plain string
+        env::set_var("POP_LAUNCHER_MY_PLUGIN_PREFIX", "env");
+        env::set_var("POP_LAUNCHER_OTHER_MAX_RESULTS", "9");
+
+        let (config, diagnostics) = load_config::<Settings>("my-plugin");
+        assert!(diagnostics.is_empty());
+        assert_eq!(config.prefix.as_deref(), Some("env"));
+        assert_eq!(config.max_results, Some(7));
+        assert_eq!(config.aliases, Some(vec!["a".to_owned(), "b".to_owned()]));
+
+        // Neither JSON nor a valid string for the field, so left to the file
+        env::set_var("POP_LAUNCHER_MY_PLUGIN_MAX_RESULTS", "many");
+        let (config, _) = load_config::<Settings>("my-plugin");
+        assert_eq!(config.max_results, Some(3));
+
+        for name in [
+            "POP_LAUNCHER_MY_PLUGIN_MAX_RESULTS",
+            "POP_LAUNCHER_MY_PLUGIN_ALIASES",
+            "POP_LAUNCHER_MY_PLUGIN_PREFIX",
+            "POP_LAUNCHER_OTHER_MAX_RESULTS",
+            "XDG_DATA_HOME",
+        ] {
+            env::remove_var(name);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! The error type shared by the plugins.
+
+use std::{io, process::ExitStatus};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0} not found in PATH")]
+    MissingTool(String),
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{0}")]
+    Unavailable(String),
+    #[error("Could not parse command {0:?}")]
+    InvalidCommand(String),
+    #[error("{program} exited with {status}{}", last_line(.stderr))]
+    Launch {
+        program: String,
+        status: ExitStatus,
+        stderr: String,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// Wraps the error of running an external program, recognising a missing executable.
+    pub fn spawn(program: &str, source: io::Error) -> Self {
+        match source.kind() {
+            io::ErrorKind::NotFound => Self::MissingTool(program.to_owned()),
+            _ => Self::Io {
+                context: format!("Could not run {program}"),
+                source,
+            },
+        }
+    }
+
+    pub fn unavailable(message: impl ToString) -> Self {
+        Self::Unavailable(message.to_string())
+    }
+}
+
+// The last line of a program's error output usually says what went wrong
+fn last_line(stderr: &str) -> String {
+    match stderr.lines().last() {
+        Some(line) => format!(": {line}"),
+        None => String::new(),
+    }
+}
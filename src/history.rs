@@ -0,0 +1,121 @@
+//! Remembers what was activated, so frequently and recently used items rank higher.
+
+use std::{
+    collections::HashMap,
+    fs::{read_to_string, write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+const MONTH: u64 = 30 * DAY;
+
+// Beyond this many uses an item does not get any more popular
+const MAX_COUNT: u32 = 10;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Usage {
+    count: u32,
+    last_used: u64,
+}
+
+/// Activation history of a single plugin, stored in
+/// `$XDG_STATE_HOME/pop-launcher/plugins/<plugin>/history.ron`.
+#[derive(Debug, Default)]
+pub struct History {
+    path: Option<PathBuf>,
+    usage: HashMap<String, Usage>,
+}
+
+impl History {
+    /// Loads the history of a plugin. A missing or unreadable file results in an empty history.
+    pub fn load(plugin_name: &str) -> Self {
+        let path = match history_file(plugin_name) {
+            Ok(path) => path,
+            Err(error) => {
+                warn!("Could not determine history location, history will not be saved: {error}");
+                return Self::default();
+            }
+        };
+
+        let usage = match read_to_string(&path) {
+            Ok(data) => ron::from_str(&data).unwrap_or_else(|error| {
+                warn!("Could not parse history file {path:?}: {error}");
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+
+        Self {
+            path: Some(path),
+            usage,
+        }
+    }
+
+    /// Records an activation of `key` and writes the history back to disk.
+    pub fn record(&mut self, key: &str) {
+        let usage = self.usage.entry(key.to_owned()).or_default();
+        usage.count = usage.count.saturating_add(1);
+        usage.last_used = now();
+
+        self.save();
+    }
+
+    /// Ranking boost for `key`, based on how often and how recently it was activated.
+    pub fn frecency(&self, key: &str) -> i64 {
+        let usage = match self.usage.get(key) {
+            Some(usage) => usage,
+            None => return 0,
+        };
+
+        let weight = match now().saturating_sub(usage.last_used) {
+            age if age < HOUR => 8,
+            age if age < DAY => 4,
+            age if age < WEEK => 2,
+            age if age < MONTH => 1,
+            _ => 0,
+        };
+
+        i64::from(usage.count.min(MAX_COUNT)) * weight
+    }
+
+    fn save(&self) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let data = match ron::to_string(&self.usage) {
+            Ok(data) => data,
+            Err(error) => {
+                warn!("Could not serialize history: {error}");
+                return;
+            }
+        };
+
+        if let Err(error) = write(path, data) {
+            warn!("Could not write history file {path:?}: {error}");
+        }
+    }
+}
+
+fn history_file(plugin_name: &str) -> anyhow::Result<PathBuf> {
+    let xdg = xdg::BaseDirectories::with_prefix("pop-launcher")?;
+    Ok(xdg.place_state_file(
+        PathBuf::from("plugins")
+            .join(plugin_name)
+            .join("history.ron"),
+    )?)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,218 @@
+//! Runs several plugins in one process, handing each query to the plugin its prefix belongs to.
+
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use log::warn;
+use pop_launcher::{PluginResponse, Request};
+
+use crate::{ContextMenu, PopLauncherPlugin, Responder};
+
+/// A plugin made of other plugins.
+///
+/// A search goes to the first plugin whose [`crate::QueryPrefix`] matches it, or else to the first
+/// plugin without a prefix. Everything else goes to the plugin that answered the last search, as
+/// the result ids the launcher refers to are its own.
+#[derive(Default)]
+pub struct Host {
+    plugins: Vec<Box<dyn PopLauncherPlugin>>,
+    active: Option<usize>,
+    responder: Responder,
+}
+
+impl Host {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a plugin, usually one from [`crate::start_plugin`]. Each keeps its own result limit.
+    pub fn with_plugin(mut self, plugin: Box<dyn PopLauncherPlugin>) -> Self {
+        self.plugins.push(plugin);
+        self
+    }
+
+    fn route(&self, input: &str) -> Option<usize> {
+        let prefixed = self.plugins.iter().position(|plugin| {
+            plugin
+                .query_prefix()
+                .is_some_and(|prefix| prefix.strip(input).is_some())
+        });
+
+        prefixed.or_else(|| {
+            self.plugins
+                .iter()
+                .position(|plugin| plugin.query_prefix().is_none())
+        })
+    }
+
+    // All plugins share one output, so there is only ever one writer with one buffer. It is lent
+    // to the active plugin in exchange for that plugin's own.
+    fn switch_to(&mut self, index: Option<usize>) {
+        if self.active == index {
+            return;
+        }
+
+        if let Some(previous) = self.active {
+            self.responder
+                .swap_output(self.plugins[previous].responder());
+        }
+        if let Some(next) = index {
+            self.responder.swap_output(self.plugins[next].responder());
+        }
+        self.active = index;
+    }
+
+    fn active(&mut self) -> Option<&mut Box<dyn PopLauncherPlugin>> {
+        self.plugins.get_mut(self.active?)
+    }
+}
+
+#[async_trait(?Send)]
+impl PopLauncherPlugin for Host {
+    async fn search(&mut self, query: &str) {
+        self.switch_to(self.route(query));
+
+        match self.active() {
+            Some(plugin) => plugin.request(Request::Search(query.to_owned())).await,
+            None => {
+                warn!("No plugin for query {query}");
+                self.responder.respond(PluginResponse::Finished).await;
+            }
+        }
+    }
+
+    async fn activate(&mut self, id: u32) {
+        if let Some(plugin) = self.active() {
+            plugin.activate(id).await;
+        }
+    }
+
+    async fn complete(&mut self, id: u32) {
+        if let Some(plugin) = self.active() {
+            plugin.complete(id).await;
+        }
+    }
+
+    async fn exit(&self) {
+        for plugin in &self.plugins {
+            plugin.exit().await;
+        }
+    }
+
+    async fn interrupt(&mut self) {
+        if let Some(plugin) = self.active() {
+            plugin.interrupt().await;
+        }
+    }
+
+    async fn quit(&self, id: u32) {
+        if let Some(plugin) = self.active.and_then(|index| self.plugins.get(index)) {
+            plugin.quit(id).await;
+        }
+    }
+
+    async fn shutdown(&mut self) {
+        for plugin in &mut self.plugins {
+            plugin.shutdown().await;
+        }
+    }
+
+    fn context_menu(&mut self) -> Option<&mut dyn ContextMenu> {
+        self.active()?.context_menu()
+    }
+
+    fn responder(&mut self) -> &mut Responder {
+        match self.active {
+            Some(index) => self.plugins[index].responder(),
+            None => &mut self.responder,
+        }
+    }
+
+    fn watched_paths(&self) -> Vec<PathBuf> {
+        self.plugins
+            .iter()
+            .flat_map(|plugin| plugin.watched_paths())
+            .collect()
+    }
+
+    async fn reload(&mut self) {
+        for plugin in &mut self.plugins {
+            plugin.reload().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pop_launcher::PluginSearchResult;
+
+    use super::*;
+    use crate::{
+        testing::{drive, summary},
+        QueryPrefix,
+    };
+
+    // Answers every search with five numbered results
+    struct Numbers {
+        query_prefix: Option<QueryPrefix>,
+        responder: Responder,
+    }
+
+    impl Numbers {
+        fn boxed(prefix: Option<&str>, limit: usize) -> Box<dyn PopLauncherPlugin> {
+            let mut responder = Responder::default();
+            responder.set_limit(limit);
+            Box::new(Self {
+                query_prefix: prefix.map(|prefix| QueryPrefix::new(prefix, Vec::new())),
+                responder,
+            })
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl PopLauncherPlugin for Numbers {
+        async fn search(&mut self, query: &str) {
+            self.responder.respond(PluginResponse::Clear).await;
+            for number in 0..5 {
+                let result = PluginSearchResult {
+                    name: format!("{query} {number}"),
+                    ..Default::default()
+                };
+                self.responder.respond(PluginResponse::Append(result)).await;
+            }
+            self.responder.respond(PluginResponse::Finished).await;
+        }
+
+        async fn activate(&mut self, _id: u32) {}
+
+        fn responder(&mut self) -> &mut Responder {
+            &mut self.responder
+        }
+
+        fn query_prefix(&self) -> Option<&QueryPrefix> {
+            self.query_prefix.as_ref()
+        }
+    }
+
+    #[tokio::test]
+    async fn plugins_keep_their_own_result_limit() {
+        let mut host = Host::new()
+            .with_plugin(Numbers::boxed(Some("n"), 1))
+            .with_plugin(Numbers::boxed(None, 2));
+
+        let mut shown = Vec::new();
+        for query in ["n a", "b", "n c"] {
+            let responses = drive(&mut host, [Request::Search(query.into())]).await;
+            shown.push(summary(&responses).join(", "));
+        }
+
+        assert_eq!(
+            shown,
+            [
+                "Clear, a 0, 4 more results…, Finished",
+                "Clear, b 0, b 1, 3 more results…, Finished",
+                "Clear, c 0, 4 more results…, Finished",
+            ]
+        );
+    }
+}
@@ -0,0 +1,371 @@
+//! Picking icons for results: from the icon theme, desktop files, file types or image files.
+//!
+//! Plugins ask for the icon of a kind of result by a key, with the theme names to try in order.
+//! The `icons` table of the plugin's config replaces them, e.g. `icons = { project = "kicad" }`,
+//! and takes theme names as well as absolute paths to image files.
+
+use std::{
+    cell::{OnceCell, RefCell},
+    collections::{HashMap, HashSet},
+    fs::{read_dir, read_to_string},
+    path::{Path, PathBuf},
+};
+
+use log::{info, warn};
+use merge::Merge;
+use pop_launcher::IconSource;
+use serde::Deserialize;
+
+use crate::load_config;
+
+// File types icon themes come in
+const ICON_EXTENSIONS: &[&str] = &["png", "svg", "xpm"];
+
+// Guessing from the extension is all file results need, they are not opened to find out more
+const FALLBACK_MIME: &str = "application/octet-stream";
+const DIRECTORY_MIME: &str = "inode/directory";
+
+thread_local! {
+    static ICON_DIRS: OnceCell<Vec<PathBuf>> = const { OnceCell::new() };
+    // Whether the themes have an icon, by name, for every name asked about so far
+    static THEME_ICONS: RefCell<HashMap<String, bool>> = RefCell::new(HashMap::new());
+    static DESKTOP_ENTRIES: OnceCell<Vec<DesktopEntry>> = const { OnceCell::new() };
+    static MIME_GLOBS: OnceCell<HashMap<String, String>> = const { OnceCell::new() };
+}
+
+#[derive(Deserialize, Merge, Default)]
+struct IconConfig {
+    icons: Option<HashMap<String, String>>,
+}
+
+/// The icons a plugin configured for its kinds of results.
+#[derive(Debug, Default)]
+pub struct Icons {
+    configured: HashMap<String, String>,
+}
+
+impl Icons {
+    pub fn load(plugin_name: &str) -> Self {
+        let (config, _) = load_config::<IconConfig>(plugin_name);
+        Self {
+            configured: config.icons.unwrap_or_default(),
+        }
+    }
+
+    /// The configured icon for `key`, otherwise the first of `candidates` the theme has.
+    pub fn get(&self, key: &str, candidates: &[&str]) -> Option<IconSource> {
+        self.configured(key).or_else(|| named(candidates))
+    }
+
+    /// The icon configured for `key`, if any.
+    pub fn configured(&self, key: &str) -> Option<IconSource> {
+        self.configured.get(key).and_then(|icon| parse(icon))
+    }
+}
+
+/// The first of `candidates` found in the installed icon themes. If none is, the first one is
+/// still returned: the launcher may know better where to look.
+pub fn named(candidates: &[&str]) -> Option<IconSource> {
+    let installed = candidates
+        .iter()
+        .find(|candidate| is_installed(candidate))
+        .copied();
+
+    installed
+        .or_else(|| candidates.first().copied())
+        .map(|name| IconSource::Name(name.to_owned().into()))
+}
+
+/// An icon given by the user: a theme name, or an absolute path to an image that exists.
+pub fn parse(icon: &str) -> Option<IconSource> {
+    let path = Path::new(icon);
+    if path.is_absolute() && !path.is_file() {
+        warn!("Icon {icon} does not exist");
+        return None;
+    }
+
+    Some(IconSource::Name(icon.to_owned().into()))
+}
+
+/// The `Icon=` of the desktop file that runs `program`, by name or path.
+pub fn for_executable(program: &str) -> Option<IconSource> {
+    let program = executable_name(program)?;
+
+    DESKTOP_ENTRIES.with(|entries| {
+        entries
+            .get_or_init(desktop_entries)
+            .iter()
+            .find(|entry| entry.executables.contains(&program))
+            .and_then(|entry| parse(&entry.icon))
+    })
+}
+
+/// The `Icon=` of the desktop file with the given id, e.g. `firefox` for `firefox.desktop`.
+pub fn for_desktop_entry(id: &str) -> Option<IconSource> {
+    let id = id.strip_suffix(".desktop").unwrap_or(id);
+
+    DESKTOP_ENTRIES.with(|entries| {
+        entries
+            .get_or_init(desktop_entries)
+            .iter()
+            .find(|entry| entry.id == id)
+            .and_then(|entry| parse(&entry.icon))
+    })
+}
+
+/// The icon of the file's type, guessed from its extension.
+pub fn for_file(path: &Path) -> IconSource {
+    let mime = if path.is_dir() {
+        DIRECTORY_MIME.to_owned()
+    } else {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| {
+                MIME_GLOBS.with(|globs| {
+                    globs
+                        .get_or_init(mime_globs)
+                        .get(&extension.to_lowercase())
+                        .cloned()
+                })
+            })
+            .unwrap_or_else(|| FALLBACK_MIME.to_owned())
+    };
+
+    IconSource::Mime(mime.into())
+}
+
+#[derive(Debug)]
+struct DesktopEntry {
+    id: String,
+    icon: String,
+    // File names of `Exec=` and `TryExec=`
+    executables: Vec<String>,
+}
+
+fn executable_name(command: &str) -> Option<String> {
+    let program = shlex::split(command)?.into_iter().next()?;
+    let name = Path::new(&program).file_name()?.to_str()?.to_owned();
+    Some(name)
+}
+
+/// Data directories, most important first.
+fn data_dirs() -> Vec<PathBuf> {
+    match xdg::BaseDirectories::new() {
+        Ok(xdg) => std::iter::once(xdg.get_data_home())
+            .chain(xdg.get_data_dirs())
+            .collect(),
+        Err(error) => {
+            warn!("Could not find data directories: {error}");
+            Vec::new()
+        }
+    }
+}
+
+fn is_installed(name: &str) -> bool {
+    if let Some(installed) = THEME_ICONS.with(|icons| icons.borrow().get(name).copied()) {
+        return installed;
+    }
+
+    let installed = ICON_DIRS.with(|dirs| {
+        dirs.get_or_init(icon_dirs).iter().any(|dir| {
+            ICON_EXTENSIONS
+                .iter()
+                .any(|extension| dir.join(format!("{name}.{extension}")).is_file())
+        })
+    });
+    THEME_ICONS.with(|icons| icons.borrow_mut().insert(name.to_owned(), installed));
+    installed
+}
+
+/// The directories icons sit in: those the installed themes list in their `index.theme`, plus
+/// the icon directories themselves and `/usr/share/pixmaps`.
+fn icon_dirs() -> Vec<PathBuf> {
+    let mut bases: Vec<PathBuf> = data_dirs().iter().map(|dir| dir.join("icons")).collect();
+    bases.extend(home::home_dir().map(|home| home.join(".icons")));
+
+    let mut dirs = Vec::new();
+    for base in bases {
+        let themes = read_dir(&base).into_iter().flatten();
+        for theme in themes.filter_map(|theme| Some(theme.ok()?.path())) {
+            if let Ok(index) = read_to_string(theme.join("index.theme")) {
+                dirs.extend(theme_directories(&index).map(|dir| theme.join(dir)));
+            }
+        }
+        dirs.push(base);
+    }
+    dirs.push(PathBuf::from("/usr/share/pixmaps"));
+
+    dirs.retain(|dir| dir.is_dir());
+    info!("Found {} icon directories", dirs.len());
+    dirs
+}
+
+// The subdirectories an `index.theme` declares
+fn theme_directories(index: &str) -> impl Iterator<Item = &str> {
+    let mut in_main_group = false;
+
+    index
+        .lines()
+        .map(str::trim)
+        .filter_map(move |line| {
+            if line.starts_with('[') {
+                in_main_group = line == "[Icon Theme]";
+                return None;
+            }
+            match line.split_once('=') {
+                Some(("Directories" | "ScaledDirectories", dirs)) if in_main_group => Some(dirs),
+                _ => None,
+            }
+        })
+        .flat_map(|dirs| dirs.split(','))
+        .map(str::trim)
+        .filter(|dir| !dir.is_empty())
+}
+
+fn desktop_entries() -> Vec<DesktopEntry> {
+    let mut entries = Vec::new();
+    let mut seen = HashSet::new();
+
+    // Earlier directories override files with the same name in later ones
+    for dir in data_dirs() {
+        let files = match read_dir(dir.join("applications")) {
+            Ok(files) => files,
+            Err(_) => continue,
+        };
+
+        for path in files.filter_map(|file| Some(file.ok()?.path())) {
+            let id = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) => match name.strip_suffix(".desktop") {
+                    Some(id) => id.to_owned(),
+                    None => continue,
+                },
+                None => continue,
+            };
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+
+            if let Some(entry) = read_to_string(&path)
+                .ok()
+                .and_then(|data| parse_desktop_entry(id, &data))
+            {
+                entries.push(entry);
+            }
+        }
+    }
+
+    entries
+}
+
+fn parse_desktop_entry(id: String, data: &str) -> Option<DesktopEntry> {
+    let mut icon = None;
+    let mut executables = Vec::new();
+    let mut in_main_group = false;
+
+    for line in data.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_main_group = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_main_group {
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some(("Icon", value)) => icon = Some(value.trim().to_owned()),
+            Some(("Exec" | "TryExec", value)) => executables.extend(executable_name(value)),
+            _ => (),
+        }
+    }
+
+    Some(DesktopEntry {
+        id,
+        icon: icon?,
+        executables,
+    })
+}
+
+fn mime_globs() -> HashMap<String, String> {
+    let mut globs = HashMap::new();
+
+    // Lines are `weight:type:glob`, heaviest first, and earlier directories take precedence
+    for dir in data_dirs() {
+        let data = match read_to_string(dir.join("mime").join("globs2")) {
+            Ok(data) => data,
+            Err(_) => continue,
+        };
+
+        for line in data.lines().filter(|line| !line.starts_with('#')) {
+            let mut fields = line.split(':');
+            let (mime, glob) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(_weight), Some(mime), Some(glob)) => (mime, glob),
+                _ => continue,
+            };
+
+            // Only plain extensions, anything fancier needs a real glob matcher
+            if let Some(extension) = glob.strip_prefix("*.") {
+                if !extension.contains(['*', '?', '[']) {
+                    globs
+                        .entry(extension.to_lowercase())
+                        .or_insert_with(|| mime.to_owned());
+                }
+            }
+        }
+    }
+
+    globs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::testing::{lock_env, TempDir};
+
+    const INDEX: &str = "\
+[Icon Theme]
+Name=Test
+Directories=48x48/apps, scalable/apps,
+
+[48x48/apps]
+Size=48
+";
+
+    #[test]
+    fn theme_directories_come_from_the_main_group() {
+        let dirs: Vec<&str> = theme_directories(INDEX).collect();
+        assert_eq!(dirs, ["48x48/apps", "scalable/apps"]);
+    }
+
+    #[test]
+    fn named_prefers_installed_icons() {
+        let _env = lock_env();
+        let data = TempDir::new();
+        data.write("icons/test/index.theme", INDEX);
+        data.write("icons/test/scalable/apps/pop-launcher-test.svg", "<svg/>");
+        // Not declared by the theme, so not found
+        data.write("icons/test/other/pop-launcher-hidden.png", "");
+        env::set_var("XDG_DATA_HOME", data.path());
+
+        let name = |icon: Option<IconSource>| match icon {
+            Some(IconSource::Name(name)) => name.into_owned(),
+            other => panic!("Unexpected icon {other:?}"),
+        };
+        assert_eq!(
+            name(named(&["pop-launcher-missing", "pop-launcher-test"])),
+            "pop-launcher-test"
+        );
+        assert_eq!(
+            name(named(&["pop-launcher-hidden", "pop-launcher-test"])),
+            "pop-launcher-test"
+        );
+        assert_eq!(
+            name(named(&["pop-launcher-missing", "pop-launcher-hidden"])),
+            "pop-launcher-missing"
+        );
+        assert!(named(&[]).is_none());
+
+        env::remove_var("XDG_DATA_HOME");
+    }
+}
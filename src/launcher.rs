@@ -0,0 +1,271 @@
+//! Starting programs on behalf of the user, detached from the plugin.
+
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    fs::{metadata, remove_file, File},
+    io::{self, Read},
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+
+use log::{error, info, warn};
+use pop_launcher::{IconSource, PluginResponse, PluginSearchResult};
+use tokio::process::{Child, Command};
+
+use crate::{notify, Error, Responder, Result, FAILURE_ID};
+
+// A program failing within this time most likely did so because of how we started it
+const EARLY_EXIT: Duration = Duration::from_millis(300);
+
+const DEFAULT_TERMINAL: &str = "x-terminal-emulator";
+
+static LAUNCHES: AtomicU32 = AtomicU32::new(0);
+
+/// A program to start, built up like a [`std::process::Command`].
+///
+/// The program runs in its own session, so it survives the plugin and the launcher, and does not
+/// receive signals meant for them.
+#[derive(Debug, Clone)]
+pub struct Launch {
+    program: String,
+    args: Vec<OsString>,
+    current_dir: Option<PathBuf>,
+    env: Vec<(OsString, OsString)>,
+    terminal: bool,
+}
+
+impl Launch {
+    pub fn new(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            current_dir: None,
+            env: Vec::new(),
+            terminal: false,
+        }
+    }
+
+    /// Splits a shell-like command line into the program and its arguments.
+    pub fn parse(command_line: &str) -> Result<Self> {
+        let mut words = shlex::split(command_line).unwrap_or_default().into_iter();
+
+        match words.next() {
+            Some(program) => Ok(Self::new(program).args(words)),
+            None => Err(Error::InvalidCommand(command_line.to_owned())),
+        }
+    }
+
+    /// Opens a file or URL with the user's preferred application.
+    pub fn open(target: impl AsRef<OsStr>) -> Self {
+        Self::new("xdg-open").arg(target)
+    }
+
+    pub fn arg(mut self, arg: impl AsRef<OsStr>) -> Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_owned()));
+        self
+    }
+
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    pub fn env(mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> Self {
+        self.env
+            .push((key.as_ref().to_owned(), value.as_ref().to_owned()));
+        self
+    }
+
+    /// Runs the program inside `$TERMINAL` (or `x-terminal-emulator`) instead of directly.
+    pub fn in_terminal(mut self, terminal: bool) -> Self {
+        self.terminal = terminal;
+        self
+    }
+
+    /// Starts the program and waits briefly to catch it failing right away.
+    ///
+    /// Programs still running after that are left alone; they are reaped in the background once
+    /// they exit.
+    pub async fn spawn(self) -> Result<()> {
+        let (program, args) = if self.terminal {
+            let terminal = env::var("TERMINAL").unwrap_or_else(|_| DEFAULT_TERMINAL.to_owned());
+            let args = [OsString::from("-e"), OsString::from(&self.program)]
+                .into_iter()
+                .chain(self.args)
+                .collect();
+            (terminal, args)
+        } else {
+            (self.program, self.args)
+        };
+
+        // Spawning in a missing directory fails just like a missing program would
+        if let Some(dir) = &self.current_dir {
+            metadata(dir).map_err(|source| Error::Io {
+                context: format!("Could not run {program} in {}", dir.display()),
+                source,
+            })?;
+        }
+
+        let (stderr, mut error_output) =
+            error_log().map_err(|error| Error::spawn(&program, error))?;
+
+        let mut command = Command::new(&program);
+        command
+            .args(&args)
+            .envs(self.env)
+            .stdin(process::Stdio::null())
+            .stdout(process::Stdio::null())
+            .stderr(stderr);
+        if let Some(dir) = self.current_dir {
+            command.current_dir(dir);
+        }
+
+        detach(&mut command);
+
+        info!("Launching {program} {args:?}");
+        let mut child = command
+            .spawn()
+            .map_err(|error| Error::spawn(&program, error))?;
+
+        let status = match tokio::time::timeout(EARLY_EXIT, child.wait()).await {
+            Ok(status) => status.map_err(|error| Error::spawn(&program, error))?,
+            Err(_) => {
+                tokio::spawn(reap(program, child));
+                return Ok(());
+            }
+        };
+
+        if status.success() {
+            return Ok(());
+        }
+
+        let mut stderr = Vec::new();
+        let _ = error_output.read_to_end(&mut stderr);
+        Err(Error::Launch {
+            stderr: String::from_utf8_lossy(&stderr).trim().to_owned(),
+            program,
+            status,
+        })
+    }
+}
+
+/// Closes the launcher after a successful launch. A failed one is logged and shown in place of
+/// the results instead, so the user learns why nothing happened.
+pub async fn finish(responder: &mut Responder, outcome: Result<()>) {
+    report(responder, outcome, "Could not launch").await;
+}
+
+// Like `finish`, describing a failure with `failure`
+pub(crate) async fn report(responder: &mut Responder, outcome: Result<()>, failure: &str) {
+    let error = match outcome {
+        Ok(()) => {
+            responder.respond(PluginResponse::Close).await;
+            return;
+        }
+        Err(error) => error,
+    };
+
+    error!("{error}");
+    notify::failure(failure, &error.to_string());
+    responder.respond(PluginResponse::Clear).await;
+    responder
+        .respond(PluginResponse::Append(PluginSearchResult {
+            // Outside of any item store and past any notice, `serve` closes the launcher for it
+            id: FAILURE_ID,
+            name: error.to_string(),
+            description: failure.to_owned(),
+            icon: Some(IconSource::Name("dialog-error".into())),
+            ..Default::default()
+        }))
+        .await;
+    responder.respond(PluginResponse::Finished).await;
+}
+
+// Runs the program in its own session
+pub(crate) fn detach(command: &mut Command) {
+    // SAFETY: setsid is async-signal-safe and we do not touch any other state of the fork
+    unsafe {
+        command.pre_exec(|| match libc::setsid() {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        });
+    }
+}
+
+// Unlike a pipe, a file keeps accepting the program's error output after the plugin exits.
+// It is unlinked right away, so it disappears together with the program.
+pub(crate) fn error_log() -> io::Result<(File, File)> {
+    let launch = LAUNCHES.fetch_add(1, Ordering::Relaxed);
+    let dir = env::var_os("XDG_RUNTIME_DIR").map_or_else(env::temp_dir, PathBuf::from);
+    let path = dir.join(format!("pop-launcher-{}-{launch}.err", process::id()));
+
+    let writer = File::create(&path)?;
+    let reader = File::open(&path);
+    remove_file(&path)?;
+
+    Ok((writer, reader?))
+}
+
+async fn reap(program: String, mut child: Child) {
+    match child.wait().await {
+        Ok(status) if !status.success() => warn!("{program} exited with {status}"),
+        Ok(_) => (),
+        Err(error) => warn!("Could not wait for {program}: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn missing_directory_is_not_a_missing_program() {
+        let outcome = Launch::new("true")
+            .current_dir("/nonexistent/directory")
+            .spawn()
+            .await;
+
+        match outcome {
+            Err(Error::Io { context, .. }) => {
+                assert_eq!(context, "Could not run true in /nonexistent/directory")
+            }
+            other => panic!("Unexpected outcome {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_program() {
+        let outcome = Launch::new("pop-launcher-missing-program").spawn().await;
+        assert!(
+            matches!(outcome, Err(Error::MissingTool(program)) if program == "pop-launcher-missing-program")
+        );
+    }
+
+    #[tokio::test]
+    async fn early_failure_carries_the_error_output() {
+        let outcome = Launch::parse("sh -c 'echo first >&2; echo broken >&2; exit 3'")
+            .unwrap()
+            .spawn()
+            .await;
+
+        match outcome {
+            Err(error @ Error::Launch { .. }) => {
+                assert_eq!(error.to_string(), "sh exited with exit status: 3: broken")
+            }
+            other => panic!("Unexpected outcome {other:?}"),
+        }
+    }
+}
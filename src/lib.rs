@@ -1,19 +1,272 @@
-use std::{fs::read_to_string, iter::once, path::PathBuf};
+use std::{collections::VecDeque, path::PathBuf};
 
 use async_trait::async_trait;
-use futures_lite::AsyncWriteExt;
-use log::warn;
+use futures_lite::{future, AsyncWrite, AsyncWriteExt, Stream, StreamExt};
+use log::{error, info, warn};
+use pop_launcher::{
+    async_stdin, async_stdout, json_input_stream, ContextOption, IconSource, PluginResponse,
+    PluginSearchResult, Request,
+};
+
+pub mod cache;
+pub mod cli;
+pub mod clipboard;
+pub mod config;
+pub mod error;
+pub mod history;
+pub mod host;
+pub mod icon;
+pub mod launcher;
+pub mod logging;
+pub mod manifest;
+pub mod notify;
+pub mod plugins;
+pub mod query;
+pub mod ranking;
+pub mod record;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod watch;
+
+use launcher::Launch;
+use manifest::Manifest;
 use merge::Merge;
-use pop_launcher::{async_stdout, PluginResponse, Request};
+use record::Recorder;
 use serde::Deserialize;
+use watch::Watcher;
+
+pub use error::{Error, Result};
+
+pub use config::{
+    get_config, get_config_candidates, get_config_files, load_config, ConfigDiagnostic,
+};
+
+/// A result the framework shows on top of every search, e.g. to point out a broken config file.
+#[derive(Debug, Clone)]
+pub struct Notice {
+    pub name: String,
+    pub description: String,
+    pub icon: Option<IconSource>,
+    /// File to open when the notice is activated.
+    pub open: Option<PathBuf>,
+}
+
+/// Results shown per search unless the config sets `max_results`.
+pub const DEFAULT_RESULT_LIMIT: usize = 50;
+
+// Appends are written once this much piled up, even if the search is still running
+const BATCH_SIZE: usize = 64 * 1024;
+
+#[derive(Deserialize, Merge, Default)]
+struct ResponderConfig {
+    max_results: Option<usize>,
+}
 
+/// Writes responses to the launcher.
+///
+/// `Clear` and `Append` are batched and only written with the next other response, normally the
+/// `Finished` of the search. Appends past the result limit are dropped; `Finished` then comes
+/// with a "N more results…" item instead, which shows the search again with a higher limit.
 pub struct Responder {
-    output: blocking::Unblock<std::io::Stdout>,
+    output: Box<dyn AsyncWrite + Unpin>,
+    // Responses not written yet. Keeping bytes the writer did not accept here also means an
+    // aborted search can never leave half a JSON line on the output.
+    pending: Vec<u8>,
+    recorder: Option<Recorder>,
+    // The start of the line the output is in the middle of, recorded once it is complete
+    sent_line: Vec<u8>,
+    // The output has the start of the first line in `pending` already
+    mid_line: bool,
+    notices: Vec<Notice>,
+    limit: Limit,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Limit {
+    base: usize,
+    current: usize,
+    // Appends of the current search, shown and dropped
+    shown: usize,
+    hidden: usize,
+}
+
+impl Default for Limit {
+    fn default() -> Self {
+        Self {
+            base: DEFAULT_RESULT_LIMIT,
+            current: DEFAULT_RESULT_LIMIT,
+            shown: 0,
+            hidden: 0,
+        }
+    }
 }
 
 impl Responder {
+    /// Creates a responder that writes JSON lines to an arbitrary writer instead of stdout.
+    pub fn new<W>(output: W) -> Self
+    where
+        W: AsyncWrite + Unpin + 'static,
+    {
+        Self {
+            output: Box::new(output),
+            pending: Vec::new(),
+            mid_line: false,
+            recorder: None,
+            sent_line: Vec::new(),
+            notices: Vec::new(),
+            limit: Limit::default(),
+        }
+    }
+
+    /// Sends the responses to another writer from now on, keeping the notices.
+    pub fn set_output<W>(&mut self, output: W)
+    where
+        W: AsyncWrite + Unpin + 'static,
+    {
+        self.output = Box::new(output);
+        self.pending.clear();
+        self.sent_line.clear();
+        self.mid_line = false;
+    }
+
+    /// Additionally writes every response the output receives to a recording.
+    pub fn set_recorder(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    // Exchanges where the responses go, but not the notices or the result limit, which belong to
+    // the plugin
+    pub(crate) fn swap_output(&mut self, other: &mut Responder) {
+        std::mem::swap(&mut self.output, &mut other.output);
+        std::mem::swap(&mut self.pending, &mut other.pending);
+        std::mem::swap(&mut self.mid_line, &mut other.mid_line);
+        std::mem::swap(&mut self.recorder, &mut other.recorder);
+        std::mem::swap(&mut self.sent_line, &mut other.sent_line);
+    }
+
+    /// Shows at most `limit` results per search.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit.base = limit.max(1);
+        self.limit.current = self.limit.base;
+    }
+
+    /// Applies the `max_results` of the plugin's config.
+    pub fn load_limit(&mut self, plugin_name: &str) {
+        let (config, _) = load_config::<ResponderConfig>(plugin_name);
+        self.set_limit(config.max_results.unwrap_or(DEFAULT_RESULT_LIMIT));
+    }
+
+    /// Whether the id belongs to the "N more results…" item.
+    pub fn is_more_results(&self, id: u32) -> bool {
+        id == MORE_RESULTS_ID
+    }
+
+    /// Lets the next search show more results, after the "N more results…" item was activated.
+    pub fn raise_limit(&mut self) {
+        self.limit.current = self.limit.current.saturating_add(self.limit.base);
+    }
+
+    /// Back to the configured limit, for a new query.
+    pub fn reset_limit(&mut self) {
+        self.limit.current = self.limit.base;
+    }
+
+    /// Replaces the plugin's own notices, which are appended right after every
+    /// `PluginResponse::Clear`, followed by one for every [`config::diagnostics`].
+    pub fn set_notices(&mut self, notices: Vec<Notice>) {
+        self.notices = notices;
+    }
+
+    /// Looks up the notice behind a result id, if the id belongs to one.
+    pub fn notice(&self, id: u32) -> Option<Notice> {
+        if id >> INDEX_BITS != GENERATION_MASK {
+            return None;
+        }
+        self.notices().into_iter().nth((id & INDEX_MASK) as usize)
+    }
+
+    fn notices(&self) -> Vec<Notice> {
+        let diagnostics = config::diagnostics();
+        self.notices
+            .iter()
+            .cloned()
+            .chain(diagnostics.iter().map(Notice::from))
+            .collect()
+    }
+
+    /// Drops what an aborted search queued but did not write yet and forgets its result count,
+    /// so the `Finished` that follows does not carry its results along.
+    pub fn discard(&mut self) {
+        let keep = match self.mid_line {
+            // The rest of a line the output already has the start of
+            true => self
+                .pending
+                .iter()
+                .position(|&byte| byte == b'\n')
+                .map_or(0, |newline| newline + 1),
+            false => 0,
+        };
+        self.pending.truncate(keep);
+        self.limit.shown = 0;
+        self.limit.hidden = 0;
+    }
+
     pub async fn respond(&mut self, response: PluginResponse) {
-        let mut data = match serde_json::to_string(&response) {
+        if matches!(response, PluginResponse::Append(_)) {
+            // Gives `serve` the chance to read a request that makes this search obsolete
+            future::yield_now().await;
+        }
+
+        let limit = &mut self.limit;
+        match response {
+            PluginResponse::Clear => {
+                limit.shown = 0;
+                limit.hidden = 0;
+            }
+            PluginResponse::Append(_) if limit.shown >= limit.current => {
+                limit.hidden += 1;
+                return;
+            }
+            PluginResponse::Append(_) => limit.shown += 1,
+            PluginResponse::Finished if limit.hidden > 0 => {
+                let more = PluginSearchResult {
+                    id: MORE_RESULTS_ID,
+                    name: match limit.hidden {
+                        1 => String::from("1 more result…"),
+                        hidden => format!("{hidden} more results…"),
+                    },
+                    description: String::from("Show more results"),
+                    icon: Some(IconSource::Name("view-more".into())),
+                    ..Default::default()
+                };
+                self.queue(&PluginResponse::Append(more));
+            }
+            _ => (),
+        }
+
+        let batched = matches!(response, PluginResponse::Clear | PluginResponse::Append(_));
+        self.queue(&response);
+
+        if matches!(response, PluginResponse::Clear) {
+            for (index, notice) in self.notices().into_iter().enumerate() {
+                let result = PluginSearchResult {
+                    id: (GENERATION_MASK << INDEX_BITS) | index as u32,
+                    name: notice.name,
+                    description: notice.description,
+                    icon: notice.icon,
+                    ..Default::default()
+                };
+                self.queue(&PluginResponse::Append(result));
+            }
+        }
+
+        if !batched || self.pending.len() >= BATCH_SIZE {
+            self.flush().await;
+        }
+    }
+
+    fn queue(&mut self, response: &PluginResponse) {
+        let mut data = match serde_json::to_string(response) {
             Ok(data) => data,
             Err(_) => {
                 warn!("Could not serialize response as json");
@@ -21,23 +274,56 @@ impl Responder {
             }
         };
         data.push('\n');
+        self.pending.extend_from_slice(data.as_bytes());
+    }
 
-        if self.output.write_all(data.as_bytes()).await.is_err() {
-            warn!("Could not write output");
-            return;
+    async fn flush(&mut self) {
+        while !self.pending.is_empty() {
+            match self.output.write(&self.pending).await {
+                Ok(written) if written > 0 => {
+                    self.mid_line = self.pending[written - 1] != b'\n';
+                    if self.recorder.is_some() {
+                        self.sent_line.extend_from_slice(&self.pending[..written]);
+                        self.record_sent();
+                    }
+                    self.pending.drain(..written);
+                }
+                _ => {
+                    warn!("Could not write output");
+                    self.pending.clear();
+                    self.sent_line.clear();
+                    self.mid_line = false;
+                    return;
+                }
+            }
         }
 
         if self.output.flush().await.is_err() {
             warn!("Could not flush output");
         };
     }
+
+    // Records the responses the output received in full. Doing it any earlier would record the
+    // ones `discard` drops, which the launcher never sees.
+    fn record_sent(&mut self) {
+        let recorder = match &self.recorder {
+            Some(recorder) => recorder,
+            None => return,
+        };
+
+        while let Some(newline) = self.sent_line.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.sent_line.drain(..=newline).collect();
+            match serde_json::from_slice::<PluginResponse>(&line) {
+                Ok(response) => recorder.response(&response),
+                Err(error) => warn!("Could not record response: {error}"),
+            }
+        }
+    }
 }
 
 impl Default for Responder {
     fn default() -> Self {
-        Self {
-            output: async_stdout(),
-        }
+        Self::new(async_stdout())
     }
 }
 
@@ -46,18 +332,65 @@ pub trait PopLauncherPlugin {
     // Required
     async fn search(&mut self, query: &str);
     async fn activate(&mut self, id: u32);
+    fn responder(&mut self) -> &mut Responder;
 
     // Optional
-    async fn activate_context(&self, _id: u32, _context: u32) {}
+    /// Called when an entry of a result's context menu was chosen, which goes to
+    /// [`PopLauncherPlugin::context_menu`].
+    async fn activate_context(&mut self, id: u32, context: u32) {
+        match self.context_menu() {
+            Some(menu) => menu.activate(id, context).await,
+            None => warn!("No context action {context} for id {id}"),
+        }
+    }
     async fn complete(&mut self, _id: u32) {}
-    async fn context(&self, _id: u32) {}
+    /// Asked for the context menu of a result, answered from [`PopLauncherPlugin::context_menu`].
+    async fn context(&mut self, id: u32) {
+        let options = self
+            .context_menu()
+            .map(|menu| menu.options(id))
+            .unwrap_or_default();
+        self.responder()
+            .respond(PluginResponse::Context { id, options })
+            .await;
+    }
+    /// The context menus of the plugin's results, normally `Some(self)` for plugins implementing
+    /// [`ContextActions`]. Without them every result gets an empty menu.
+    fn context_menu(&mut self) -> Option<&mut dyn ContextMenu> {
+        None
+    }
     async fn exit(&self) {}
-    async fn interrupt(&self) {}
+    /// Called when the running search was aborted, either by `Request::Interrupt` or because a
+    /// newer search came in.
+    async fn interrupt(&mut self) {}
     async fn quit(&self, _id: u32) {}
+    /// Called once after the request loop has stopped, either because of `Request::Exit` or
+    /// because the launcher closed our input.
+    async fn shutdown(&mut self) {}
+    /// Files and directories the plugin's configuration is read from. Whenever one of them
+    /// changes, [`PopLauncherPlugin::reload`] is called before the next search.
+    fn watched_paths(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+    async fn reload(&mut self) {}
+    /// The prefix queries for this plugin start with. It is stripped before
+    /// [`PopLauncherPlugin::search`] is called, and searches without it are ignored.
+    fn query_prefix(&self) -> Option<&QueryPrefix> {
+        None
+    }
 
     async fn request(&mut self, req: Request) {
         match req {
-            Request::Search(input) => self.search(&input).await,
+            Request::Search(input) => {
+                match self.query_prefix().map(|prefix| prefix.strip(&input)) {
+                    None => self.search(&input).await,
+                    Some(Some(query)) => self.search(query).await,
+                    Some(None) => {
+                        warn!("Search query did not match: {input}");
+                        self.responder().respond(PluginResponse::Finished).await;
+                    }
+                }
+            }
             Request::Activate(id) => self.activate(id).await,
 
             Request::ActivateContext { id, context } => self.activate_context(id, context).await,
@@ -70,34 +403,831 @@ pub trait PopLauncherPlugin {
     }
 }
 
-pub fn get_config<Config>(files: &[PathBuf]) -> Config
+#[derive(Deserialize, Merge, Default)]
+struct QueryPrefixConfig {
+    prefix: Option<String>,
+    aliases: Option<Vec<String>>,
+}
+
+/// The word a query has to start with to reach a plugin, plus any aliases for it.
+///
+/// Defaults to the prefix of the plugin's [`Manifest`] and can be changed with the `prefix` and
+/// `aliases` keys of its config file. pop-launcher only forwards queries matching the regex in
+/// `plugin.ron`, so the plugin has to be installed again after changing them.
+#[derive(Debug, Clone)]
+pub struct QueryPrefix {
+    prefix: String,
+    aliases: Vec<String>,
+}
+
+impl QueryPrefix {
+    pub fn new(prefix: impl Into<String>, aliases: Vec<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            aliases,
+        }
+    }
+
+    /// Loads the configured prefix of a plugin, `None` if it neither declares nor configures one.
+    pub fn load(manifest: &Manifest) -> Option<Self> {
+        let (config, _) = load_config::<QueryPrefixConfig>(manifest.id);
+        let prefix = config
+            .prefix
+            .or_else(|| manifest.prefix.map(str::to_owned))?;
+
+        Some(Self::new(prefix, config.aliases.unwrap_or_default()))
+    }
+
+    /// The prefix used when filling in the query for the user.
+    pub fn primary(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn all(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.prefix.as_str()).chain(self.aliases.iter().map(String::as_str))
+    }
+
+    /// The query without its prefix, or `None` if it does not start with one.
+    pub fn strip<'a>(&self, input: &'a str) -> Option<&'a str> {
+        self.all().find_map(|prefix| {
+            let rest = input.strip_prefix(prefix)?;
+            if rest.is_empty() {
+                Some(rest)
+            } else {
+                rest.strip_prefix(' ')
+            }
+        })
+    }
+}
+
+/// Sets up logging, then feeds every request from stdin into the plugin until the launcher asks
+/// us to exit or closes the input stream.
+///
+/// The plugin is constructed after the logger is up, so anything logged during initialisation
+/// ends up in the log as well. If initialisation fails the plugin keeps running in degraded
+/// mode, answering every search with the reason instead of disappearing from the launcher.
+///
+/// Given command line arguments, the plugin runs a single query from the terminal instead, see
+/// [`cli::USAGE`].
+pub async fn run_plugin<P, F>(plugin_name: &str, init: F)
+where
+    P: PopLauncherPlugin,
+    F: FnOnce() -> Result<P>,
+{
+    let cli = match cli::Options::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(message) => {
+            eprintln!("{message}");
+            std::process::exit(2);
+        }
+    };
+
+    match cli {
+        Some(_) => logging::init_stderr(plugin_name),
+        None => logging::init(plugin_name),
+    }
+    apply_settings(plugin_name);
+    info!("Loaded pop launcher {plugin_name} plugin");
+
+    match init() {
+        Ok(mut plugin) => run(plugin_name, &mut plugin, cli).await,
+        Err(error) => {
+            error!("Could not initialise {plugin_name} plugin, running degraded: {error}");
+            run(plugin_name, &mut Degraded::new(plugin_name, error), cli).await
+        }
+    }
+
+    notify::shutdown();
+    info!("Stopping pop launcher {plugin_name} plugin");
+}
+
+async fn run<P: PopLauncherPlugin>(plugin_name: &str, plugin: &mut P, cli: Option<cli::Options>) {
+    plugin.responder().load_limit(plugin_name);
+    if let Some(options) = cli {
+        return cli::run(plugin, options).await;
+    }
+
+    let input = json_input_stream(async_stdin());
+    match Recorder::load(plugin_name) {
+        Some(recorder) => {
+            plugin.responder().set_recorder(recorder.clone());
+            let input = input.inspect(move |request| {
+                if let Ok(request) = request {
+                    recorder.request(request);
+                }
+            });
+            serve_plugin(Some(plugin_name), plugin, input).await
+        }
+        None => serve_plugin(Some(plugin_name), plugin, input).await,
+    }
+}
+
+/// Initialises a plugin the way [`run_plugin`] does, for running it next to others or picking it
+/// at runtime: it gets the result limit of its config, and a [`Degraded`] one stands in if it
+/// fails.
+pub fn start_plugin<P, F>(plugin_name: &str, init: F) -> Box<dyn PopLauncherPlugin>
+where
+    P: PopLauncherPlugin + 'static,
+    F: FnOnce() -> Result<P>,
+{
+    let mut plugin: Box<dyn PopLauncherPlugin> = match init() {
+        Ok(plugin) => Box::new(plugin),
+        Err(error) => {
+            error!("Could not initialise {plugin_name} plugin, running degraded: {error}");
+            Box::new(Degraded::new(plugin_name, error))
+        }
+    };
+    plugin.responder().load_limit(plugin_name);
+    plugin
+}
+
+// The settings of the whole process, which the plugin's `reload` does not know about
+fn apply_settings(plugin_name: &str) {
+    logging::apply_level(plugin_name);
+    notify::init(plugin_name);
+    clipboard::init(plugin_name);
+}
+
+/// Stands in for a plugin that failed to initialise, explaining why on every search.
+pub struct Degraded {
+    plugin_name: String,
+    error: Error,
+    query_prefix: Option<QueryPrefix>,
+    responder: Responder,
+}
+
+impl Degraded {
+    pub fn new(plugin_name: &str, error: Error) -> Self {
+        Self {
+            plugin_name: plugin_name.to_owned(),
+            error,
+            query_prefix: manifest::find(plugin_name).and_then(QueryPrefix::load),
+            responder: Responder::default(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl PopLauncherPlugin for Degraded {
+    async fn search(&mut self, _query: &str) {
+        self.responder.respond(PluginResponse::Clear).await;
+        self.responder
+            .respond(PluginResponse::Append(PluginSearchResult {
+                name: self.error.to_string(),
+                description: format!("The {} plugin could not start", self.plugin_name),
+                icon: Some(IconSource::Name("dialog-error".into())),
+                ..Default::default()
+            }))
+            .await;
+        self.responder.respond(PluginResponse::Finished).await;
+    }
+
+    async fn activate(&mut self, _id: u32) {}
+
+    fn responder(&mut self) -> &mut Responder {
+        &mut self.responder
+    }
+
+    fn query_prefix(&self) -> Option<&QueryPrefix> {
+        self.query_prefix.as_ref()
+    }
+
+    fn watched_paths(&self) -> Vec<PathBuf> {
+        get_config_candidates(&self.plugin_name).unwrap_or_default()
+    }
+
+    async fn reload(&mut self) {
+        self.query_prefix = manifest::find(&self.plugin_name).and_then(QueryPrefix::load);
+        self.responder.load_limit(&self.plugin_name);
+    }
+}
+
+/// The request loop behind [`run_plugin`], usable with any stream of decoded requests.
+///
+/// Searches run while the loop keeps reading input: a new `Request::Search`, `Request::Interrupt`
+/// or `Request::Exit` drops the running search, so it cannot append anything afterwards. Other
+/// requests arriving mid-search are queued and handled once the search is done. Searches are
+/// preempted whenever they wait, which every `Append` does.
+///
+/// Before every search the plugin's [`PopLauncherPlugin::watched_paths`] are checked, and the
+/// plugin reloaded if they changed.
+pub async fn serve<P, S>(plugin: &mut P, requests: S)
+where
+    P: PopLauncherPlugin + ?Sized,
+    S: Stream<Item = serde_json::Result<Request>> + Unpin,
+{
+    serve_plugin(None, plugin, requests).await
+}
+
+// `serve`, also watching the config of `plugin_name` for the settings `run_plugin` applied
+async fn serve_plugin<P, S>(plugin_name: Option<&str>, plugin: &mut P, requests: S)
+where
+    P: PopLauncherPlugin + ?Sized,
+    S: Stream<Item = serde_json::Result<Request>> + Unpin,
+{
+    // Searches may run into the end of the input, the requests queued before it are still handled
+    let mut requests = requests.fuse();
+    let mut pending = VecDeque::new();
+
+    let mut watched = plugin.watched_paths();
+    if let Some(plugin_name) = plugin_name {
+        watched.extend(get_config_candidates(plugin_name).unwrap_or_default());
+    }
+    watched.sort();
+    watched.dedup();
+    let mut watcher = Watcher::new(watched);
+    let mut last_query = String::new();
+
+    loop {
+        let request = match pending.pop_front() {
+            Some(request) => request,
+            None => match next_request(&mut requests).await {
+                Some(request) => request,
+                None => break,
+            },
+        };
+
+        match request {
+            Request::Search(query) => {
+                if watcher.changed() {
+                    info!("Configuration changed, reloading");
+                    if let Some(plugin_name) = plugin_name {
+                        apply_settings(plugin_name);
+                    }
+                    plugin.reload().await;
+                }
+
+                plugin.responder().reset_limit();
+                last_query.clone_from(&query);
+                search(plugin, query, &mut requests, &mut pending).await;
+            }
+            Request::Activate(id) if plugin.responder().is_more_results(id) => {
+                plugin.responder().raise_limit();
+                let query = last_query.clone();
+                search(plugin, query, &mut requests, &mut pending).await;
+            }
+            Request::Exit => {
+                plugin.request(Request::Exit).await;
+                break;
+            }
+            Request::Activate(id) if plugin.responder().notice(id).is_some() => {
+                activate_notice(plugin.responder(), id).await;
+            }
+            Request::Activate(FAILURE_ID) => {
+                plugin.responder().respond(PluginResponse::Close).await;
+            }
+            request => plugin.request(request).await,
+        }
+    }
+
+    plugin.shutdown().await;
+}
+
+/// Runs a single search until it finishes or is preempted.
+async fn search<P, S>(
+    plugin: &mut P,
+    query: String,
+    requests: &mut S,
+    pending: &mut VecDeque<Request>,
+) where
+    P: PopLauncherPlugin + ?Sized,
+    S: Stream<Item = serde_json::Result<Request>> + Unpin,
+{
+    let preempted_by = {
+        let mut search = plugin.request(Request::Search(query));
+
+        loop {
+            let next = future::or(
+                async {
+                    (&mut search).await;
+                    None
+                },
+                async { Some(next_request(requests).await) },
+            )
+            .await;
+
+            match next {
+                None => break None,
+                Some(None) => {
+                    search.await;
+                    return;
+                }
+                Some(Some(request @ (Request::Search(_) | Request::Interrupt | Request::Exit))) => {
+                    break Some(request)
+                }
+                Some(Some(request)) => pending.push_back(request),
+            }
+        }
+    };
+
+    if let Some(request) = preempted_by {
+        info!("Aborting search in favour of {request:?}");
+        plugin.interrupt().await;
+        plugin.responder().discard();
+
+        match request {
+            // Nobody else is going to finish this search for the launcher
+            Request::Interrupt => plugin.responder().respond(PluginResponse::Finished).await,
+            request => pending.push_front(request),
+        }
+    }
+}
+
+pub(crate) async fn activate_notice(responder: &mut Responder, id: u32) {
+    let outcome = match responder.notice(id).and_then(|notice| notice.open) {
+        Some(path) => Launch::open(path).spawn().await,
+        None => Ok(()),
+    };
+
+    launcher::finish(responder, outcome).await;
+}
+
+async fn next_request<S>(requests: &mut S) -> Option<Request>
 where
-    Config: Default + Merge + for<'a> Deserialize<'a>,
+    S: Stream<Item = serde_json::Result<Request>> + Unpin,
 {
-    let mut config = Config::default();
+    while let Some(request_res) = requests.next().await {
+        match request_res {
+            Ok(request) => return Some(request),
+            Err(_error) => warn!("Error occured when retrieving requests."),
+        }
+    }
+
+    None
+}
+
+// Result ids carry the generation of the search that produced them in their upper bits
+const INDEX_BITS: u32 = 20;
+const INDEX_MASK: u32 = (1 << INDEX_BITS) - 1;
+const GENERATION_MASK: u32 = u32::MAX >> INDEX_BITS;
+// Past the notices, the "N more results…" item and the item describing a failed launch
+const MORE_RESULTS_ID: u32 = (GENERATION_MASK << INDEX_BITS) | (INDEX_MASK - 1);
+const FAILURE_ID: u32 = (GENERATION_MASK << INDEX_BITS) | INDEX_MASK;
+
+/// A named entry in the context menu of a result, e.g. "Open folder".
+///
+/// `A` is the plugin's own type describing what to do, typically a fieldless enum, which
+/// [`ItemStore::context_action`] hands back once the entry is chosen.
+#[derive(Debug, Clone)]
+pub struct ContextAction<A> {
+    pub name: String,
+    pub action: A,
+}
+
+impl<A> ContextAction<A> {
+    pub fn new(name: impl Into<String>, action: A) -> Self {
+        Self {
+            name: name.into(),
+            action,
+        }
+    }
+}
+
+/// Answers context requests, see [`PopLauncherPlugin::context_menu`].
+#[async_trait(?Send)]
+pub trait ContextMenu {
+    /// The entries of the context menu of result `id`.
+    fn options(&self, id: u32) -> Vec<ContextOption>;
+    /// Carries out entry `context` of the context menu of result `id`.
+    async fn activate(&mut self, id: u32, context: u32);
+}
 
-    for path in files {
-        if let Ok(data) = read_to_string(path) {
-            if let Ok(conf) = ron::from_str::<Config>(&data) {
-                config.merge(conf);
+/// Context menus for plugins keeping their results in an [`ItemStore`]: a result's menu lists the
+/// actions it was appended with, and choosing one calls [`ContextActions::handle_action`].
+#[async_trait(?Send)]
+pub trait ContextActions {
+    type Item: Clone;
+    type Action: Clone;
+
+    fn items(&self) -> &ItemStore<Self::Item, Self::Action>;
+    async fn handle_action(&mut self, item: Self::Item, action: Self::Action);
+}
+
+#[async_trait(?Send)]
+impl<P: ContextActions> ContextMenu for P {
+    fn options(&self, id: u32) -> Vec<ContextOption> {
+        self.items().context_options(id)
+    }
+
+    async fn activate(&mut self, id: u32, context: u32) {
+        let chosen = self
+            .items()
+            .context_action(id, context)
+            .map(|(item, action)| (item.clone(), action.clone()));
+
+        if let Some((item, action)) = chosen {
+            self.handle_action(item, action).await;
+        }
+    }
+}
+
+/// The items behind the results of the current search, along with their context actions.
+///
+/// Every search starts a new generation and ids handed out by [`ItemStore::append`] encode it, so
+/// an `Activate` or `Complete` for a result of an older search is rejected instead of resolving to
+/// whatever item now happens to sit at the same position.
+pub struct ItemStore<T, A = ()> {
+    generation: u32,
+    items: Vec<(T, Vec<ContextAction<A>>)>,
+}
+
+impl<T, A> Default for ItemStore<T, A> {
+    fn default() -> Self {
+        Self {
+            generation: 0,
+            items: Vec::new(),
+        }
+    }
+}
+
+impl<T, A> ItemStore<T, A> {
+    /// Drops the items of the previous search and tells the launcher to clear its results.
+    pub async fn clear(&mut self, responder: &mut Responder) {
+        // The last generation is reserved for notices
+        self.generation = (self.generation + 1) % GENERATION_MASK;
+        self.items.clear();
+        responder.respond(PluginResponse::Clear).await;
+    }
+
+    /// Stores `item` and sends `result` to the launcher under the id assigned to it. Once a
+    /// search has used up every id nothing more is stored or sent, and `None` is returned.
+    pub async fn append(
+        &mut self,
+        responder: &mut Responder,
+        item: T,
+        result: PluginSearchResult,
+    ) -> Option<u32> {
+        self.append_with_actions(responder, item, Vec::new(), result)
+            .await
+    }
+
+    /// Like [`ItemStore::append`], offering `actions` in the context menu of the result.
+    pub async fn append_with_actions(
+        &mut self,
+        responder: &mut Responder,
+        item: T,
+        actions: Vec<ContextAction<A>>,
+        mut result: PluginSearchResult,
+    ) -> Option<u32> {
+        let id = self.insert_with_actions(item, actions)?;
+        result.id = id;
+        responder.respond(PluginResponse::Append(result)).await;
+        Some(id)
+    }
+
+    /// Stores `item` without emitting anything, returning its id, or `None` if the search has
+    /// no ids left.
+    pub fn insert(&mut self, item: T) -> Option<u32> {
+        self.insert_with_actions(item, Vec::new())
+    }
+
+    fn insert_with_actions(&mut self, item: T, actions: Vec<ContextAction<A>>) -> Option<u32> {
+        let index = u32::try_from(self.items.len())
+            .ok()
+            .filter(|index| *index <= INDEX_MASK)?;
+        if index == INDEX_MASK {
+            warn!("Too many items in a single search, dropping the rest");
+        }
+
+        self.items.push((item, actions));
+        Some((self.generation << INDEX_BITS) | index)
+    }
+
+    /// Looks up the item behind a result id, if it belongs to the current search.
+    pub fn get(&self, id: u32) -> Option<&T> {
+        self.entry(id).map(|(item, _)| item)
+    }
+
+    /// The context menu of the item. Items without actions, and unknown ids, get an empty one.
+    pub fn context_options(&self, id: u32) -> Vec<ContextOption> {
+        self.entry(id)
+            .map(|(_, actions)| {
+                actions
+                    .iter()
+                    .enumerate()
+                    .map(|(index, action)| ContextOption {
+                        id: index as u32,
+                        name: action.name.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Resolves `Request::ActivateContext` to the item and the action chosen for it.
+    pub fn context_action(&self, id: u32, context: u32) -> Option<(&T, &A)> {
+        let (item, actions) = self.entry(id)?;
+        match actions.get(context as usize) {
+            Some(action) => Some((item, &action.action)),
+            None => {
+                warn!("No context action {context} for id {id}");
+                None
             }
         }
     }
 
-    config
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn entry(&self, id: u32) -> Option<&(T, Vec<ContextAction<A>>)> {
+        if id >> INDEX_BITS != self.generation {
+            warn!("Ignoring id {id} from an earlier search");
+            return None;
+        }
+
+        let entry = self.items.get((id & INDEX_MASK) as usize);
+        if entry.is_none() {
+            warn!("No item with id {id}");
+        }
+        entry
+    }
 }
 
-pub fn get_config_files(plugin_name: &str) -> anyhow::Result<Vec<PathBuf>> {
-    let xdg = xdg::BaseDirectories::with_prefix("pop-launcher")?;
-    let home = xdg.get_data_home();
-    let dirs = xdg.get_data_dirs();
+#[cfg(test)]
+mod tests {
+    use futures_lite::stream;
 
-    Ok(dirs
-        .into_iter()
-        .chain(once(home))
-        .filter_map(|dir| {
-            let plugin_config = dir.join("plugins").join(plugin_name).join("config.ron");
-            plugin_config.exists().then_some(plugin_config)
-        })
-        .collect())
+    use super::*;
+    use crate::{
+        cli::MemoryOutput,
+        testing::{drive, summary, TempDir},
+    };
+
+    // Answers every search with `results` numbered results
+    struct Counting {
+        results: usize,
+        responder: Responder,
+    }
+
+    impl Counting {
+        fn new(results: usize) -> Self {
+            Self {
+                results,
+                responder: Responder::default(),
+            }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl PopLauncherPlugin for Counting {
+        async fn search(&mut self, query: &str) {
+            self.responder.respond(PluginResponse::Clear).await;
+            for index in 0..self.results {
+                let result = PluginSearchResult {
+                    name: format!("{query} {index}"),
+                    ..Default::default()
+                };
+                self.responder.respond(PluginResponse::Append(result)).await;
+            }
+            self.responder.respond(PluginResponse::Finished).await;
+        }
+
+        async fn activate(&mut self, _id: u32) {}
+
+        fn responder(&mut self) -> &mut Responder {
+            &mut self.responder
+        }
+    }
+
+    #[tokio::test]
+    async fn newer_search_drops_the_running_one() {
+        let mut plugin = Counting::new(2);
+        let responses = drive(
+            &mut plugin,
+            [Request::Search("a".into()), Request::Search("b".into())],
+        )
+        .await;
+
+        assert_eq!(summary(&responses), ["Clear", "b 0", "b 1", "Finished"]);
+    }
+
+    #[tokio::test]
+    async fn interrupt_finishes_without_the_aborted_results() {
+        let mut plugin = Counting::new(10);
+        plugin.responder.set_limit(2);
+        let output = MemoryOutput::default();
+        plugin.responder.set_output(output.clone());
+
+        // The interrupt only arrives once the search has appended more than the limit
+        let requests =
+            stream::iter([Request::Search("a".into()), Request::Interrupt]).then(|request| async {
+                for _ in 0..5 {
+                    future::yield_now().await;
+                }
+                Ok(request)
+            });
+        serve(&mut plugin, Box::pin(requests)).await;
+
+        assert_eq!(summary(&output.take_responses()), ["Finished"]);
+    }
+
+    #[tokio::test]
+    async fn recordings_leave_out_what_a_newer_search_dropped() {
+        let dir = TempDir::new();
+        let path = dir.path().join("recording.jsonl");
+        let mut plugin = Counting::new(3);
+        plugin
+            .responder
+            .set_recorder(Recorder::open(&path).unwrap());
+
+        let responses = drive(
+            &mut plugin,
+            [Request::Search("a".into()), Request::Search("b".into())],
+        )
+        .await;
+
+        let recorded: Vec<PluginResponse> = record::read(&path)
+            .unwrap()
+            .into_iter()
+            .filter_map(|entry| match entry.event {
+                record::Event::Response(response) => Some(response),
+                record::Event::Request(_) => None,
+            })
+            .collect();
+        assert_eq!(summary(&recorded), summary(&responses));
+        assert_eq!(
+            summary(&recorded),
+            ["Clear", "b 0", "b 1", "b 2", "Finished"]
+        );
+    }
+
+    #[tokio::test]
+    async fn other_requests_do_not_abort_the_search() {
+        let mut plugin = Counting::new(1);
+        let responses = drive(&mut plugin, [Request::Search("a".into()), Request::Quit(0)]).await;
+
+        assert_eq!(summary(&responses), ["Clear", "a 0", "Finished"]);
+    }
+
+    // One item per search, with a single context action whose choices it remembers
+    #[derive(Default)]
+    struct WithMenu {
+        items: ItemStore<String, &'static str>,
+        chosen: Vec<(String, &'static str)>,
+        responder: Responder,
+    }
+
+    #[async_trait(?Send)]
+    impl PopLauncherPlugin for WithMenu {
+        async fn search(&mut self, query: &str) {
+            self.items.clear(&mut self.responder).await;
+            let actions = vec![ContextAction::new("Copy", "copy")];
+            self.items
+                .append_with_actions(
+                    &mut self.responder,
+                    query.to_owned(),
+                    actions,
+                    PluginSearchResult::default(),
+                )
+                .await;
+            self.responder.respond(PluginResponse::Finished).await;
+        }
+
+        async fn activate(&mut self, _id: u32) {}
+
+        fn context_menu(&mut self) -> Option<&mut dyn ContextMenu> {
+            Some(self)
+        }
+
+        fn responder(&mut self) -> &mut Responder {
+            &mut self.responder
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl ContextActions for WithMenu {
+        type Item = String;
+        type Action = &'static str;
+
+        fn items(&self) -> &ItemStore<String, &'static str> {
+            &self.items
+        }
+
+        async fn handle_action(&mut self, item: String, action: &'static str) {
+            self.chosen.push((item, action));
+        }
+    }
+
+    fn context_options(responses: &[PluginResponse]) -> Vec<Vec<String>> {
+        responses
+            .iter()
+            .filter_map(|response| match response {
+                PluginResponse::Context { options, .. } => {
+                    Some(options.iter().map(|option| option.name.clone()).collect())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn context_requests_go_to_the_item_store() {
+        let mut plugin = WithMenu::default();
+        let responses = drive(&mut plugin, [Request::Search("a".into())]).await;
+        let id = match responses.as_slice() {
+            [PluginResponse::Clear, PluginResponse::Append(result), PluginResponse::Finished] => {
+                result.id
+            }
+            other => panic!("Unexpected responses {other:?}"),
+        };
+
+        let responses = drive(
+            &mut plugin,
+            [
+                Request::Context(id),
+                Request::ActivateContext { id, context: 0 },
+                Request::ActivateContext { id, context: 1 },
+            ],
+        )
+        .await;
+
+        assert_eq!(context_options(&responses), [["Copy"]]);
+        assert_eq!(plugin.chosen, [("a".to_owned(), "copy")]);
+    }
+
+    #[tokio::test]
+    async fn activating_a_failed_launch_closes_the_launcher() {
+        let mut plugin = Counting::new(0);
+        plugin.responder.set_output(MemoryOutput::default());
+        launcher::finish(&mut plugin.responder, Err(Error::unavailable("Broken"))).await;
+
+        let responses = drive(&mut plugin, [Request::Activate(FAILURE_ID)]).await;
+        assert_eq!(summary(&responses), ["Close"]);
+    }
+
+    #[tokio::test]
+    async fn plugins_without_context_menus_answer_with_empty_ones() {
+        let mut plugin = Degraded::new("test", Error::unavailable("Broken"));
+        let responses = drive(&mut plugin, [Request::Context(3)]).await;
+
+        assert_eq!(context_options(&responses), [Vec::<String>::new()]);
+    }
+
+    #[tokio::test]
+    async fn item_ids_expire_with_their_search() {
+        let mut responder = Responder::new(MemoryOutput::default());
+        let mut items = ItemStore::<&str>::default();
+
+        items.clear(&mut responder).await;
+        let first = items.insert("first").unwrap();
+        let second = items.insert("second").unwrap();
+        assert_eq!(items.get(first), Some(&"first"));
+        assert_eq!(items.get(second), Some(&"second"));
+        assert_eq!(items.get(second + 1), None);
+
+        items.clear(&mut responder).await;
+        let third = items.insert("third").unwrap();
+        assert_eq!(third & INDEX_MASK, first & INDEX_MASK);
+        assert_eq!(items.get(first), None);
+        assert_eq!(items.get(third), Some(&"third"));
+        assert_eq!(items.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn item_generations_skip_the_one_of_notices() {
+        let mut responder = Responder::new(MemoryOutput::default());
+        let mut items = ItemStore::<()>::default();
+
+        for _ in 0..=GENERATION_MASK {
+            items.clear(&mut responder).await;
+            let id = items.insert(()).unwrap();
+            assert_ne!(id >> INDEX_BITS, GENERATION_MASK);
+            assert_ne!(id, MORE_RESULTS_ID);
+        }
+    }
+
+    #[tokio::test]
+    async fn items_past_the_last_id_are_dropped() {
+        let mut responder = Responder::new(MemoryOutput::default());
+        let mut items = ItemStore::<usize>::default();
+
+        items.clear(&mut responder).await;
+        for index in 0..=INDEX_MASK as usize {
+            assert!(items.insert(index).is_some());
+        }
+        let last = (items.generation << INDEX_BITS) | INDEX_MASK;
+        assert_eq!(items.insert(0), None);
+        assert_eq!(items.len(), INDEX_MASK as usize + 1);
+        assert_eq!(items.get(last), Some(&(INDEX_MASK as usize)));
+
+        items.clear(&mut responder).await;
+        assert!(items.insert(0).is_some());
+    }
+
+    #[test]
+    fn query_prefixes_need_a_space_before_the_query() {
+        let prefix = QueryPrefix::new("rs", vec!["rust".to_owned()]);
+        assert_eq!(prefix.strip("rs vec"), Some("vec"));
+        assert_eq!(prefix.strip("rust vec"), Some("vec"));
+        assert_eq!(prefix.strip("rs"), Some(""));
+        assert_eq!(prefix.strip("rsvec"), None);
+        assert_eq!(prefix.strip("vec"), None);
+        assert_eq!(prefix.all().collect::<Vec<_>>(), ["rs", "rust"]);
+        assert_eq!(prefix.primary(), "rs");
+    }
 }
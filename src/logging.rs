@@ -0,0 +1,231 @@
+//! Logger setup for the plugins: journald when it is there, stderr or a file otherwise.
+//!
+//! Journald support needs the `journald` feature, without it plugins log to stderr instead.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{stderr, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use merge::Merge;
+use serde::Deserialize;
+
+use crate::load_config;
+
+#[cfg(feature = "journald")]
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// The logging related keys of a plugin's config file.
+///
+/// Like any other key these can be overridden with `POP_LAUNCHER_<PLUGIN>_LOG_LEVEL` and
+/// `POP_LAUNCHER_<PLUGIN>_LOG_FILE`.
+#[derive(Deserialize, Merge, Default)]
+struct LoggingConfig {
+    log_level: Option<String>,
+    log_file: Option<PathBuf>,
+}
+
+/// Installs a logger for the plugin. Never fails: if the configured backend cannot be used we
+/// fall back to the next one, ending with stderr.
+pub fn init(plugin_name: &str) {
+    let logger = install(plugin_name);
+    let (config, _) = load_config::<LoggingConfig>(plugin_name);
+
+    let backend = match config.log_file.as_deref().map(open_log_file) {
+        Some(Ok(file)) => Backend::Stream(Box::new(file)),
+        Some(Err(error)) => {
+            eprintln!("{plugin_name}: Could not open log file, logging to stderr: {error}");
+            Backend::Stream(Box::new(stderr()))
+        }
+        #[cfg(feature = "journald")]
+        None if Path::new(JOURNALD_SOCKET).exists() => Backend::Journald,
+        None => Backend::Stream(Box::new(stderr())),
+    };
+
+    start(logger, &config, backend);
+}
+
+/// Installs a logger writing to stderr whatever the config says, for running in a terminal.
+pub fn init_stderr(plugin_name: &str) {
+    let logger = install(plugin_name);
+    let (config, _) = load_config::<LoggingConfig>(plugin_name);
+    start(logger, &config, Backend::Stream(Box::new(stderr())));
+}
+
+/// Applies the configured `log_level` to the installed logger, after the config changed.
+pub fn apply_level(plugin_name: &str) {
+    let (config, _) = load_config::<LoggingConfig>(plugin_name);
+    log::set_max_level(level(&config));
+}
+
+fn level(config: &LoggingConfig) -> LevelFilter {
+    config
+        .log_level
+        .as_deref()
+        .and_then(|level| LevelFilter::from_str(level).ok())
+        .unwrap_or(LevelFilter::Info)
+}
+
+// The logger has to be there before the config is loaded, or the problems found in it are lost.
+// Until the config picks a backend it keeps everything.
+fn install(plugin_name: &str) -> Option<&'static PluginLogger> {
+    let logger: &'static PluginLogger = Box::leak(Box::new(PluginLogger::new(plugin_name)));
+    log::set_logger(logger).ok()?;
+    log::set_max_level(LevelFilter::Trace);
+    Some(logger)
+}
+
+fn start(logger: Option<&PluginLogger>, config: &LoggingConfig, backend: Backend) {
+    if let Some(logger) = logger {
+        let level = level(config);
+        logger.start(level, backend);
+        log::set_max_level(level);
+    }
+}
+
+enum Backend {
+    #[cfg(feature = "journald")]
+    Journald,
+    // Never stdout: that belongs to the launcher protocol
+    Stream(Box<dyn Write + Send>),
+}
+
+impl Backend {
+    fn log(&mut self, plugin_name: &str, record: &Record) {
+        match self {
+            #[cfg(feature = "journald")]
+            Backend::Journald => systemd::journal::JournalLog.log(record),
+            Backend::Stream(output) => {
+                let _ = writeln!(
+                    output,
+                    "[{} {}] {}",
+                    record.level(),
+                    plugin_name,
+                    record.args()
+                );
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        match self {
+            #[cfg(feature = "journald")]
+            Backend::Journald => {}
+            Backend::Stream(output) => {
+                let _ = output.flush();
+            }
+        }
+    }
+}
+
+fn open_log_file(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+enum Output {
+    Buffer(Vec<(Level, String)>),
+    Backend(Backend),
+}
+
+struct PluginLogger {
+    plugin_name: String,
+    output: Mutex<Output>,
+}
+
+impl PluginLogger {
+    fn new(plugin_name: &str) -> Self {
+        Self {
+            plugin_name: plugin_name.to_owned(),
+            output: Mutex::new(Output::Buffer(Vec::new())),
+        }
+    }
+
+    // Switches to `backend`, passing on what was logged until now that `level` lets through
+    fn start(&self, level: LevelFilter, mut backend: Backend) {
+        if let Ok(mut output) = self.output.lock() {
+            if let Output::Buffer(buffered) = &*output {
+                for (record_level, message) in buffered {
+                    if *record_level <= level {
+                        backend.log(
+                            &self.plugin_name,
+                            &Record::builder()
+                                .level(*record_level)
+                                .args(format_args!("{message}"))
+                                .build(),
+                        );
+                    }
+                }
+            }
+            *output = Output::Backend(backend);
+        }
+    }
+}
+
+impl Log for PluginLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        if let Ok(mut output) = self.output.lock() {
+            match &mut *output {
+                Output::Buffer(buffered) => {
+                    buffered.push((record.level(), record.args().to_string()));
+                }
+                Output::Backend(backend) => backend.log(&self.plugin_name, record),
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut output) = self.output.lock() {
+            if let Output::Backend(backend) = &mut *output {
+                backend.flush();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn log(logger: &PluginLogger, level: Level, message: &str) {
+        logger.log(
+            &Record::builder()
+                .level(level)
+                .args(format_args!("{message}"))
+                .build(),
+        );
+    }
+
+    #[test]
+    fn keeps_what_is_logged_before_the_backend_is_chosen() {
+        let dir = TempDir::new();
+        let path = dir.path().join("log");
+        let logger = PluginLogger::new("test");
+
+        log(
+            &logger,
+            Level::Warn,
+            "Ignoring config file 1:10: expected usize",
+        );
+        log(&logger, Level::Debug, "Loaded config");
+        let file = open_log_file(&path).unwrap();
+        logger.start(LevelFilter::Info, Backend::Stream(Box::new(file)));
+        log(&logger, Level::Info, "Started");
+        logger.flush();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[WARN test] Ignoring config file 1:10: expected usize\n[INFO test] Started\n"
+        );
+    }
+}
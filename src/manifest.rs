@@ -0,0 +1,163 @@
+//! What pop-launcher needs to know about each plugin, declared once and written to `plugin.ron`.
+
+use std::borrow::Cow;
+
+use pop_launcher::IconSource;
+use ron::{extensions::Extensions, ser::PrettyConfig};
+use serde::Serialize;
+
+use crate::QueryPrefix;
+
+/// Metadata of a plugin. The installer turns this into the plugin's `plugin.ron`.
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    /// Name of the binary, the plugin directory and the config/state directories.
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub icon: Option<IconSource>,
+    /// Word the query has to start with by default, e.g. `kicad` for `kicad amplifier`.
+    pub prefix: Option<&'static str>,
+    /// Hide the results of other plugins while this one is queried.
+    pub isolate: bool,
+    /// Keep our own result order instead of letting the launcher sort.
+    pub no_sort: bool,
+}
+
+pub const COMMANDO: Manifest = Manifest {
+    id: "commando",
+    name: "Commando",
+    description: "Run custom commands",
+    icon: Some(IconSource::Name(Cow::Borrowed("utilities-terminal"))),
+    prefix: None,
+    isolate: false,
+    no_sort: false,
+};
+
+pub const KICAD: Manifest = Manifest {
+    id: "kicad",
+    name: "KiCad",
+    description: "Open KiCad projects\nExample: kicad amplifier",
+    icon: Some(IconSource::Name(Cow::Borrowed("kicad"))),
+    prefix: Some("kicad"),
+    isolate: true,
+    no_sort: true,
+};
+
+pub const MPRIS: Manifest = Manifest {
+    id: "mpris",
+    name: "Media players",
+    description: "Control media players\nExample: media spotify pause",
+    icon: Some(IconSource::Name(Cow::Borrowed("folder-music"))),
+    prefix: Some("media"),
+    isolate: true,
+    no_sort: true,
+};
+
+pub const NOTMUCH: Manifest = Manifest {
+    id: "notmuch",
+    name: "Notmuch",
+    description: "Search mail threads\nExample: notmuch from:alice",
+    icon: Some(IconSource::Name(Cow::Borrowed("mail-unread"))),
+    prefix: Some("notmuch"),
+    isolate: true,
+    no_sort: true,
+};
+
+pub const RUST: Manifest = Manifest {
+    id: "rust",
+    name: "Rust documentation",
+    description: "Search the local Rust documentation\nExample: rust std::collections::Hash",
+    icon: Some(IconSource::Name(Cow::Borrowed("text-x-rust"))),
+    prefix: Some("rust"),
+    isolate: true,
+    no_sort: true,
+};
+
+/// All of [`PLUGINS`] in a single process, to be installed instead of them.
+///
+/// pop-launcher reads `isolate` and `no_sort` once per installed plugin, and the host has to get
+/// every query, so it can declare neither. Queries for the prefixed plugins then show the results
+/// of pop-launcher's other plugins as well, and the launcher sorts all results itself. Install
+/// the plugins one by one to keep their own settings.
+pub const HOST: Manifest = Manifest {
+    id: "host",
+    name: "Plugins",
+    description: "Commando, KiCad, media players, notmuch and Rust documentation in one plugin",
+    icon: Some(IconSource::Name(Cow::Borrowed("system-run"))),
+    prefix: None,
+    isolate: false,
+    no_sort: false,
+};
+
+/// Every plugin included in this build, see the cargo features.
+pub const PLUGINS: &[Manifest] = &[
+    #[cfg(feature = "commando")]
+    COMMANDO,
+    #[cfg(feature = "kicad")]
+    KICAD,
+    #[cfg(feature = "mpris")]
+    MPRIS,
+    #[cfg(feature = "notmuch")]
+    NOTMUCH,
+    #[cfg(feature = "rust")]
+    RUST,
+];
+
+pub fn find(id: &str) -> Option<&'static Manifest> {
+    PLUGINS
+        .iter()
+        .chain([&HOST])
+        .find(|manifest| manifest.id == id)
+}
+
+// Mirrors the `plugin.ron` schema of pop-launcher
+#[derive(Serialize)]
+struct PluginRon<'a> {
+    name: &'a str,
+    description: &'a str,
+    bin: PluginBinary<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon: Option<&'a IconSource>,
+    query: PluginQuery,
+}
+
+#[derive(Serialize)]
+struct PluginBinary<'a> {
+    path: &'a str,
+}
+
+#[derive(Serialize)]
+struct PluginQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    help: Option<String>,
+    isolate: bool,
+    no_sort: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    regex: Option<String>,
+}
+
+impl Manifest {
+    /// Renders the `plugin.ron` pop-launcher reads to discover the plugin, routing queries that
+    /// start with `prefix` (usually the configured [`QueryPrefix::load`]) to it.
+    pub fn to_ron(&self, prefix: Option<&QueryPrefix>) -> Result<String, ron::Error> {
+        let plugin = PluginRon {
+            name: self.name,
+            description: self.description,
+            bin: PluginBinary { path: self.id },
+            icon: self.icon.as_ref(),
+            query: PluginQuery {
+                help: prefix.map(|prefix| format!("{} ", prefix.primary())),
+                isolate: self.isolate,
+                no_sort: self.no_sort,
+                regex: prefix.map(|prefix| {
+                    let alternatives = prefix.all().map(regex::escape).collect::<Vec<_>>();
+                    format!("^(({}) )+", alternatives.join("|"))
+                }),
+            },
+        };
+
+        let config = PrettyConfig::new().extensions(Extensions::IMPLICIT_SOME);
+        ron::ser::to_string_pretty(&plugin, config)
+    }
+}
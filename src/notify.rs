@@ -0,0 +1,295 @@
+//! Desktop notifications through `org.freedesktop.Notifications` on the session bus.
+//!
+//! Used to tell the user about things that happen after the launcher has closed, most of all
+//! activations that failed. Failures are notified unless the plugin's config sets
+//! `notify = false`, successes only with `notify_success = true`.
+//!
+//! Builds without the `notifications` feature have no D-Bus and only log what they would send.
+
+use std::cell::RefCell;
+#[cfg(feature = "notifications")]
+use std::{
+    collections::HashMap,
+    sync::mpsc::{channel, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+#[cfg(feature = "notifications")]
+use dbus::{
+    arg::{RefArg, Variant},
+    blocking::Connection,
+};
+use log::{info, warn};
+use merge::Merge;
+use serde::Deserialize;
+
+use crate::load_config;
+
+#[cfg(feature = "notifications")]
+const DESTINATION: &str = "org.freedesktop.Notifications";
+#[cfg(feature = "notifications")]
+const PATH: &str = "/org/freedesktop/Notifications";
+#[cfg(feature = "notifications")]
+const INTERFACE: &str = "org.freedesktop.Notifications";
+
+// How long the notification thread waits for the daemon to answer
+#[cfg(feature = "notifications")]
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+const URGENCY_NORMAL: u8 = 1;
+const URGENCY_CRITICAL: u8 = 2;
+
+thread_local! {
+    static NOTIFIER: RefCell<Option<Notifier>> = const { RefCell::new(None) };
+}
+
+#[derive(Deserialize, Merge, Default)]
+struct NotifyConfig {
+    notify: Option<bool>,
+    notify_success: Option<bool>,
+}
+
+struct Notifier {
+    app_name: String,
+    failures: bool,
+    successes: bool,
+    // Started on first use, most plugin runs never notify anything
+    #[cfg(feature = "notifications")]
+    worker: Option<Worker>,
+}
+
+// Talks to the notification daemon on a thread of its own, so a slow or missing daemon never
+// holds up the plugin
+#[cfg(feature = "notifications")]
+struct Worker {
+    sender: Sender<Notification>,
+    thread: JoinHandle<()>,
+}
+
+#[cfg(feature = "notifications")]
+struct Notification {
+    summary: String,
+    body: String,
+    icon: &'static str,
+    urgency: u8,
+}
+
+/// Sets up notifications for the plugin, or applies a changed config. Called by
+/// [`crate::run_plugin`].
+pub fn init(plugin_name: &str) {
+    let (config, _) = load_config::<NotifyConfig>(plugin_name);
+    // Notifications already on their way are still delivered by the same thread
+    #[cfg(feature = "notifications")]
+    let worker = NOTIFIER.with(|cell| cell.borrow_mut().as_mut()?.worker.take());
+
+    let notifier = Notifier {
+        app_name: plugin_name.to_owned(),
+        failures: config.notify.unwrap_or(true),
+        successes: config.notify.unwrap_or(true) && config.notify_success.unwrap_or(false),
+        #[cfg(feature = "notifications")]
+        worker,
+    };
+    NOTIFIER.with(|cell| *cell.borrow_mut() = Some(notifier));
+}
+
+/// Waits for the notifications sent so far to be delivered. Called by [`crate::run_plugin`]
+/// before the plugin exits.
+pub fn shutdown() {
+    #[cfg(feature = "notifications")]
+    {
+        let worker = NOTIFIER.with(|cell| cell.borrow_mut().as_mut()?.worker.take());
+        if let Some(Worker { sender, thread }) = worker {
+            drop(sender);
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Tells the user that something they asked for did not work.
+pub fn failure(summary: &str, body: &str) {
+    send(
+        summary,
+        body,
+        "dialog-error",
+        URGENCY_CRITICAL,
+        |notifier| notifier.failures,
+    );
+}
+
+/// Confirms that something worked, if the user opted into such notifications.
+pub fn success(summary: &str, body: &str) {
+    send(
+        summary,
+        body,
+        "dialog-information",
+        URGENCY_NORMAL,
+        |notifier| notifier.successes,
+    );
+}
+
+fn send(
+    summary: &str,
+    body: &str,
+    icon: &'static str,
+    urgency: u8,
+    enabled: fn(&Notifier) -> bool,
+) {
+    NOTIFIER.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let notifier = match cell.as_mut() {
+            Some(notifier) if enabled(notifier) => notifier,
+            _ => return,
+        };
+
+        if let Err(error) = notifier.notify(summary, body, icon, urgency) {
+            warn!("Could not send notification {summary:?}: {error}");
+        }
+    });
+}
+
+#[cfg(feature = "notifications")]
+impl Notifier {
+    fn notify(
+        &mut self,
+        summary: &str,
+        body: &str,
+        icon: &'static str,
+        urgency: u8,
+    ) -> std::io::Result<()> {
+        let worker = match &mut self.worker {
+            Some(worker) => worker,
+            worker => worker.insert(Worker::start(self.app_name.clone())?),
+        };
+
+        let notification = Notification {
+            summary: summary.to_owned(),
+            body: body.to_owned(),
+            icon,
+            urgency,
+        };
+        if worker.sender.send(notification).is_err() {
+            warn!("Notification thread is gone, not sending {summary:?}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "notifications")]
+impl Worker {
+    fn start(app_name: String) -> std::io::Result<Self> {
+        let (sender, receiver) = channel::<Notification>();
+        let thread = thread::Builder::new()
+            .name(String::from("notify"))
+            .spawn(move || {
+                // Connected on the first notification, and again after an error
+                let mut connection = None;
+                for notification in receiver {
+                    if let Err(error) = deliver(&mut connection, &app_name, &notification) {
+                        warn!(
+                            "Could not send notification {:?}: {error}",
+                            notification.summary
+                        );
+                        connection = None;
+                    }
+                }
+            })?;
+
+        Ok(Self { sender, thread })
+    }
+}
+
+#[cfg(feature = "notifications")]
+fn deliver(
+    connection: &mut Option<Connection>,
+    app_name: &str,
+    notification: &Notification,
+) -> Result<(), dbus::Error> {
+    let connection = match connection {
+        Some(connection) => connection,
+        connection => connection.insert(Connection::new_session()?),
+    };
+
+    let mut hints: HashMap<&str, Variant<Box<dyn RefArg>>> = HashMap::new();
+    hints.insert("urgency", Variant(Box::new(notification.urgency)));
+
+    let proxy = connection.with_proxy(DESTINATION, PATH, TIMEOUT);
+    let (id,): (u32,) = proxy.method_call(
+        INTERFACE,
+        "Notify",
+        (
+            app_name,
+            0u32,
+            notification.icon,
+            notification.summary.as_str(),
+            notification.body.as_str(),
+            Vec::<&str>::new(),
+            hints,
+            -1i32,
+        ),
+    )?;
+
+    info!("Sent notification {id}: {}", notification.summary);
+    Ok(())
+}
+
+#[cfg(not(feature = "notifications"))]
+impl Notifier {
+    fn notify(
+        &mut self,
+        summary: &str,
+        _body: &str,
+        _icon: &'static str,
+        _urgency: u8,
+    ) -> Result<(), std::convert::Infallible> {
+        info!(
+            "Built without notifications, not sending {summary:?} for {}",
+            self.app_name
+        );
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "notifications"))]
+mod tests {
+    use std::{
+        env,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::testing::{lock_env, NotificationDaemon, PrivateBus};
+
+    #[test]
+    fn failures_reach_the_notification_daemon() {
+        let _env = lock_env();
+        let bus = match PrivateBus::start() {
+            Some(bus) => bus,
+            None => return eprintln!("No dbus-daemon, skipping"),
+        };
+        let daemon = NotificationDaemon::start(bus.address()).unwrap();
+        env::set_var("DBUS_SESSION_BUS_ADDRESS", bus.address());
+
+        init("test");
+        failure("Could not launch", "kicad not found in PATH");
+        // Off by default
+        success("Copied", "path");
+        shutdown();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while daemon.received().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        env::remove_var("DBUS_SESSION_BUS_ADDRESS");
+
+        assert_eq!(
+            daemon.received(),
+            [crate::testing::Notification {
+                app_name: String::from("test"),
+                icon: String::from("dialog-error"),
+                summary: String::from("Could not launch"),
+                body: String::from("kicad not found in PATH"),
+            }]
+        );
+    }
+}
@@ -0,0 +1,396 @@
+//! Runs commands the user declared in TOML files in `$XDG_CONFIG_HOME/commando/commandos/` and
+//! the `commando/commandos/` directory of every `$XDG_CONFIG_DIRS` entry.
+
+use std::{
+    collections::HashMap,
+    fs::{read_dir, read_to_string},
+    iter::once,
+    path::PathBuf,
+};
+
+use async_trait::async_trait;
+use pop_launcher::{PluginResponse, PluginSearchResult};
+
+use crate::{
+    history::History,
+    icon,
+    launcher::{self, Launch},
+    ranking::*,
+    *,
+};
+use log::{info, warn};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct CommandFile {
+    commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Command {
+    name: String,
+    command: String,
+    icon: Option<String>,
+    #[serde(default)]
+    description: String,
+    keywords: Option<Vec<String>>,
+    /// Run the command inside a terminal emulator.
+    #[serde(default)]
+    terminal: bool,
+    working_dir: Option<PathBuf>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Action {
+    RunInTerminal,
+}
+
+pub struct CommandoPlugin {
+    commands: Vec<Command>,
+    items: ItemStore<Command, Action>,
+    ranker: Ranker,
+    history: History,
+    query_prefix: Option<QueryPrefix>,
+    responder: Responder,
+}
+
+impl CommandoPlugin {
+    pub fn new() -> Self {
+        let mut plugin = Self {
+            commands: Vec::new(),
+            items: ItemStore::default(),
+            responder: Responder::default(),
+            ranker: Ranker::default(),
+            history: History::load(manifest::COMMANDO.id),
+            query_prefix: None,
+        };
+        plugin.load();
+        plugin
+    }
+
+    fn load(&mut self) {
+        self.query_prefix = QueryPrefix::load(&manifest::COMMANDO);
+        let (commands, diagnostics) = get_commands();
+        self.commands = commands;
+        self.responder
+            .set_notices(diagnostics.iter().map(Notice::from).collect());
+    }
+}
+
+impl CommandoPlugin {
+    async fn run(&mut self, command: &Command, terminal: bool) {
+        self.history.record(&command.name);
+
+        let outcome = match Launch::parse(&command.command) {
+            Ok(launch) => {
+                let mut launch = launch.in_terminal(terminal);
+                if let Some(dir) = &command.working_dir {
+                    launch = launch.current_dir(dir);
+                }
+                for (key, value) in &command.env {
+                    launch = launch.env(key, value);
+                }
+                launch.spawn().await
+            }
+            Err(error) => Err(error),
+        };
+
+        launcher::finish(&mut self.responder, outcome).await;
+    }
+}
+
+impl Default for CommandoPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl PopLauncherPlugin for CommandoPlugin {
+    async fn search(&mut self, query: &str) {
+        self.items.clear(&mut self.responder).await;
+
+        info!("Starting search with pattern: {query}");
+
+        let matches = self.ranker.rank(query, self.commands.iter(), |command| {
+            Fields::new(&command.name)
+                .description(&command.description)
+                .keywords(command.keywords.as_deref().unwrap_or_default())
+                .boost(self.history.frecency(&command.name))
+        });
+
+        for command in matches {
+            let result = PluginSearchResult {
+                name: command.name.clone(),
+                description: command.description.clone(),
+                keywords: command.keywords.clone(),
+                icon: match &command.icon {
+                    Some(icon) => icon::parse(icon),
+                    None => icon::for_executable(&command.command),
+                },
+                ..Default::default()
+            };
+            let actions = match command.terminal {
+                true => Vec::new(),
+                false => vec![ContextAction::new("Run in terminal", Action::RunInTerminal)],
+            };
+            self.items
+                .append_with_actions(&mut self.responder, command.clone(), actions, result)
+                .await;
+        }
+
+        self.responder.respond(PluginResponse::Finished).await;
+    }
+
+    async fn activate(&mut self, id: u32) {
+        let item = match self.items.get(id) {
+            Some(item) => item,
+            None => return,
+        };
+
+        info!("Activating {item:?}");
+        let item = item.clone();
+        self.run(&item, item.terminal).await;
+    }
+
+    fn context_menu(&mut self) -> Option<&mut dyn ContextMenu> {
+        Some(self)
+    }
+
+    fn responder(&mut self) -> &mut Responder {
+        &mut self.responder
+    }
+
+    fn query_prefix(&self) -> Option<&QueryPrefix> {
+        self.query_prefix.as_ref()
+    }
+
+    fn watched_paths(&self) -> Vec<PathBuf> {
+        let mut paths = get_command_dirs().unwrap_or_default();
+        paths.extend(get_config_candidates(manifest::COMMANDO.id).unwrap_or_default());
+        paths
+    }
+
+    async fn reload(&mut self) {
+        self.load();
+        self.responder.load_limit(manifest::COMMANDO.id);
+    }
+}
+
+#[async_trait(?Send)]
+impl ContextActions for CommandoPlugin {
+    type Item = Command;
+    type Action = Action;
+
+    fn items(&self) -> &ItemStore<Command, Action> {
+        &self.items
+    }
+
+    async fn handle_action(&mut self, item: Command, action: Action) {
+        info!("Activating {action:?} on {item:?}");
+        match action {
+            Action::RunInTerminal => self.run(&item, true).await,
+        }
+    }
+}
+
+fn get_command_dirs() -> anyhow::Result<Vec<PathBuf>> {
+    let xdg = xdg::BaseDirectories::with_prefix("commando")?;
+    let home = xdg.get_config_home();
+    let dirs = xdg.get_config_dirs();
+
+    Ok(dirs
+        .into_iter()
+        .chain(once(home))
+        .map(|dir| dir.join("commandos"))
+        .collect())
+}
+
+fn get_command_paths() -> anyhow::Result<Vec<PathBuf>> {
+    Ok(get_command_dirs()?
+        .into_iter()
+        .filter_map(|command_dir| {
+            let files = read_dir(command_dir).ok()?;
+            Some(files.filter_map(|file| Some(file.ok()?.path())))
+        })
+        .flatten()
+        .filter(|path| path.is_file())
+        .collect())
+}
+
+fn get_commands() -> (Vec<Command>, Vec<ConfigDiagnostic>) {
+    let paths = match get_command_paths() {
+        Ok(paths) => paths,
+        Err(error) => {
+            warn!("Could not find command directories: {error}");
+            return Default::default();
+        }
+    };
+
+    let mut commands = Vec::new();
+    let mut diagnostics = Vec::new();
+
+    for path in paths {
+        let data = match read_to_string(&path) {
+            Ok(data) => data,
+            Err(error) => {
+                diagnostics.push(ConfigDiagnostic::new(&path, error));
+                continue;
+            }
+        };
+
+        match toml::from_str::<CommandFile>(&data) {
+            Ok(file) => commands.extend(file.commands),
+            Err(error) => diagnostics.push(ConfigDiagnostic::toml(&path, &data, error)),
+        }
+    }
+
+    for diagnostic in &diagnostics {
+        warn!("Ignoring command file {diagnostic}");
+    }
+
+    (commands, diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::{stream, StreamExt};
+    use pop_launcher::Request;
+
+    use super::*;
+    use crate::{
+        cli::MemoryOutput,
+        testing::{drive, result_id, summary, XdgHome},
+    };
+
+    const COMMANDS: &str = r#"
+[[commands]]
+name = "Greet"
+command = "true"
+description = "Says nothing at all"
+keywords = ["hello"]
+
+[[commands]]
+name = "Green screen"
+command = "true --green"
+
+[[commands]]
+name = "Monitor"
+command = "top"
+terminal = true
+"#;
+
+    fn home() -> XdgHome {
+        let home = XdgHome::new();
+        home.write("config/commando/commandos/tools.toml", COMMANDS);
+        home
+    }
+
+    fn context_names(responses: &[PluginResponse]) -> Vec<String> {
+        match responses {
+            [PluginResponse::Context { options, .. }] => {
+                options.iter().map(|option| option.name.clone()).collect()
+            }
+            other => panic!("Unexpected responses {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn searches_the_declared_commands() {
+        let _home = home();
+        let mut plugin = CommandoPlugin::new();
+
+        let responses = drive(&mut plugin, [Request::Search("gre".into())]).await;
+        assert_eq!(
+            summary(&responses),
+            ["Clear", "Greet", "Green screen", "Finished"]
+        );
+
+        let responses = drive(&mut plugin, [Request::Search("hello".into())]).await;
+        assert_eq!(summary(&responses), ["Clear", "Greet", "Finished"]);
+    }
+
+    #[tokio::test]
+    async fn offers_terminals_for_commands_not_run_in_one() {
+        let _home = home();
+        let mut plugin = CommandoPlugin::new();
+        let results = drive(&mut plugin, [Request::Search(String::new())]).await;
+
+        let greet = result_id(&results, "Greet");
+        let responses = drive(&mut plugin, [Request::Context(greet)]).await;
+        assert_eq!(context_names(&responses), ["Run in terminal"]);
+
+        let monitor = result_id(&results, "Monitor");
+        let responses = drive(&mut plugin, [Request::Context(monitor)]).await;
+        assert!(context_names(&responses).is_empty());
+    }
+
+    #[tokio::test]
+    async fn activated_commands_run_and_rank_higher() {
+        let _home = home();
+        let mut plugin = CommandoPlugin::new();
+        let responses = drive(&mut plugin, [Request::Search("gre".into())]).await;
+        assert_eq!(
+            summary(&responses),
+            ["Clear", "Greet", "Green screen", "Finished"]
+        );
+
+        let screen = result_id(&responses, "Green screen");
+        let responses = drive(&mut plugin, [Request::Activate(screen)]).await;
+        assert_eq!(summary(&responses), ["Close"]);
+
+        let responses = drive(&mut plugin, [Request::Search("gre".into())]).await;
+        assert_eq!(
+            summary(&responses),
+            ["Clear", "Green screen", "Greet", "Finished"]
+        );
+    }
+
+    #[tokio::test]
+    async fn broken_command_files_show_up_as_notices() {
+        let home = home();
+        home.write(
+            "config/commando/commandos/broken.toml",
+            "[[commands]]\nname = ",
+        );
+        let mut plugin = CommandoPlugin::new();
+
+        let responses = drive(&mut plugin, [Request::Search("greet".into())]).await;
+        assert_eq!(
+            summary(&responses),
+            ["Clear", "Config error in broken.toml", "Greet", "Finished"]
+        );
+        match &responses[1] {
+            PluginResponse::Append(notice) => assert_eq!(
+                notice.description,
+                "2:8: TOML parse error at line 2, column 8"
+            ),
+            other => panic!("Unexpected response {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn applies_config_changes_before_the_next_search() {
+        let home = home();
+        let mut plugin = CommandoPlugin::new();
+        let output = MemoryOutput::default();
+        plugin.responder.set_output(output.clone());
+
+        // Written after `serve` looked at the config, like an edit while the plugin runs
+        let requests = stream::iter([Request::Search("run gre".into())]).then(|request| async {
+            home.write(
+                "data/pop-launcher/plugins/commando/config.toml",
+                "prefix = \"run\"\nmax_results = 1",
+            );
+            Ok(request)
+        });
+        serve(&mut plugin, Box::pin(requests)).await;
+
+        assert_eq!(
+            summary(&output.take_responses()),
+            ["Clear", "Greet", "1 more result…", "Finished"]
+        );
+    }
+}
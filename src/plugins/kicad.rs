@@ -0,0 +1,384 @@
+//! Finds KiCad projects and opens them in KiCad.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use merge::Merge;
+use pop_launcher::{PluginResponse, PluginSearchResult};
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::Cache,
+    clipboard,
+    history::History,
+    icon::{self, Icons},
+    launcher::{self, Launch},
+    ranking::*,
+    *,
+};
+
+#[derive(Deserialize, Merge, Default)]
+struct Config {
+    path: Option<String>,
+}
+
+// Projects can live anywhere below the search path, too deep to watch for changes, so the index
+// is rescanned this often instead
+const MAX_INDEX_AGE: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KicadProject {
+    path: PathBuf,
+    name: String,
+}
+
+impl KicadProject {
+    fn key(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+
+    fn folder(&self) -> &Path {
+        self.path.parent().unwrap_or(&self.path)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Action {
+    OpenFolder,
+    CopyPath,
+}
+
+pub struct KicadPlugin {
+    search_path: String,
+    projects: Option<Cache<Vec<KicadProject>>>,
+    query_prefix: Option<QueryPrefix>,
+    icons: Icons,
+    items: ItemStore<KicadProject, Action>,
+    ranker: Ranker,
+    history: History,
+    responder: Responder,
+}
+
+#[async_trait(?Send)]
+impl PopLauncherPlugin for KicadPlugin {
+    async fn search(&mut self, query: &str) {
+        self.items.clear(&mut self.responder).await;
+
+        info!("Starting search with pattern: {query}");
+
+        let projects = match &mut self.projects {
+            Some(projects) => {
+                let search_path = self.search_path.clone();
+                projects.refresh(move || get_projects(&search_path)).await;
+                projects.get().map(Vec::as_slice).unwrap_or_default()
+            }
+            None => &[],
+        };
+
+        let matches = self.ranker.rank(query, projects.iter(), |project| {
+            Fields::new(&project.name).boost(self.history.frecency(&project.key()))
+        });
+
+        for project in matches {
+            let result = PluginSearchResult {
+                name: project.name.clone(),
+                icon: self
+                    .icons
+                    .configured("project")
+                    .or_else(|| icon::for_executable("kicad"))
+                    .or_else(|| Some(icon::for_file(&project.path))),
+                ..Default::default()
+            };
+            let actions = vec![
+                ContextAction::new("Open folder", Action::OpenFolder),
+                clipboard::copy_action("path", Action::CopyPath),
+            ];
+            self.items
+                .append_with_actions(&mut self.responder, project.clone(), actions, result)
+                .await;
+        }
+
+        self.responder.respond(PluginResponse::Finished).await;
+    }
+
+    async fn activate(&mut self, id: u32) {
+        let item = match self.items.get(id) {
+            Some(item) => item,
+            None => return,
+        };
+
+        info!("Activating {item:?}");
+        self.history.record(&item.key());
+        let outcome = Launch::new("kicad")
+            .arg(&item.path)
+            .current_dir(item.folder())
+            .spawn()
+            .await;
+
+        launcher::finish(&mut self.responder, outcome).await;
+    }
+
+    fn context_menu(&mut self) -> Option<&mut dyn ContextMenu> {
+        Some(self)
+    }
+
+    fn responder(&mut self) -> &mut Responder {
+        &mut self.responder
+    }
+
+    fn watched_paths(&self) -> Vec<PathBuf> {
+        get_config_candidates(manifest::KICAD.id).unwrap_or_default()
+    }
+
+    async fn reload(&mut self) {
+        if let Err(error) = self.load() {
+            error!("Could not reload projects: {error}");
+        }
+        // Rescanning is the one way to pick up new projects before the index expires
+        if let Some(projects) = &mut self.projects {
+            projects.invalidate();
+        }
+        self.responder.load_limit(manifest::KICAD.id);
+    }
+
+    fn query_prefix(&self) -> Option<&QueryPrefix> {
+        self.query_prefix.as_ref()
+    }
+}
+
+#[async_trait(?Send)]
+impl ContextActions for KicadPlugin {
+    type Item = KicadProject;
+    type Action = Action;
+
+    fn items(&self) -> &ItemStore<KicadProject, Action> {
+        &self.items
+    }
+
+    async fn handle_action(&mut self, item: KicadProject, action: Action) {
+        info!("Activating {action:?} on {item:?}");
+        match action {
+            Action::OpenFolder => {
+                let outcome = Launch::open(item.folder()).spawn().await;
+                launcher::finish(&mut self.responder, outcome).await;
+            }
+            Action::CopyPath => {
+                clipboard::finish(&mut self.responder, &item.path.to_string_lossy()).await;
+            }
+        }
+    }
+}
+
+impl KicadPlugin {
+    pub fn new() -> Result<Self> {
+        let mut plugin = Self {
+            search_path: String::new(),
+            projects: None,
+            query_prefix: None,
+            icons: Icons::default(),
+            items: ItemStore::default(),
+            responder: Responder::default(),
+            ranker: Ranker::default(),
+            history: History::load(manifest::KICAD.id),
+        };
+        plugin.load()?;
+        Ok(plugin)
+    }
+
+    /// Reads the config and loads the projects in the configured directory, scanning for them if
+    /// they are not cached yet.
+    fn load(&mut self) -> Result<()> {
+        let (config, _) = load_config::<Config>(manifest::KICAD.id);
+        self.query_prefix = QueryPrefix::load(&manifest::KICAD);
+        self.icons = Icons::load(manifest::KICAD.id);
+
+        let path = get_search_path(config)
+            .ok_or_else(|| Error::unavailable("Could not find configured or home directory"))?;
+
+        let mut projects = Cache::load(manifest::KICAD.id, "projects", vec![PathBuf::from(&path)])
+            .max_age(MAX_INDEX_AGE)
+            .ignore_changes();
+        projects.ensure(|| get_projects(&path))?;
+
+        self.projects = Some(projects);
+        self.search_path = path;
+        Ok(())
+    }
+}
+
+fn get_search_path(config: Config) -> Option<String> {
+    match config.path {
+        Some(path) if PathBuf::from(&path).exists() => Some(path),
+        _ => {
+            warn!("Falling back to homedir");
+            home::home_dir().and_then(|path| path.to_str().map(|x| x.to_owned()))
+        }
+    }
+}
+
+fn get_projects(search: &str) -> Result<Vec<KicadProject>> {
+    let output = Command::new("fd")
+        .arg("kicad_pro")
+        .arg(search)
+        .output()
+        .map_err(|error| Error::spawn("fd", error))?;
+    // What a failed run found is incomplete, and would be kept as the index until it expires
+    if !output.status.success() {
+        return Err(Error::Launch {
+            program: String::from("fd"),
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        });
+    }
+    let paths = String::from_utf8_lossy(&output.stdout);
+
+    Ok(paths
+        .split_terminator('\n')
+        .filter_map(|path| {
+            let path = PathBuf::from(&path);
+            let name = get_project_name(&path)?;
+            Some(KicadProject { path, name })
+        })
+        .collect())
+}
+
+fn get_project_name(path: &Path) -> Option<String> {
+    Some(String::from(path.file_stem()?.to_str()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use pop_launcher::Request;
+
+    use super::*;
+    use crate::testing::{drive, result_id, summary, XdgHome};
+
+    // `fd` need not be installed, so the index is seeded the way an earlier run would leave it
+    fn seed_projects(home: &XdgHome, names: &[&str]) -> String {
+        let search_path = home.write("projects/.keep", "");
+        let search_path = search_path.parent().unwrap().to_string_lossy().into_owned();
+
+        let projects: Vec<KicadProject> = names
+            .iter()
+            .map(|name| KicadProject {
+                path: home.write(format!("projects/{name}/{name}.kicad_pro"), "{}"),
+                name: name.to_string(),
+            })
+            .collect();
+        Cache::load(
+            manifest::KICAD.id,
+            "projects",
+            vec![PathBuf::from(&search_path)],
+        )
+        .ensure(move || Ok(projects))
+        .unwrap();
+
+        search_path
+    }
+
+    #[tokio::test]
+    async fn searches_the_projects_below_the_configured_path() {
+        let home = XdgHome::new();
+        let search_path = seed_projects(&home, &["amplifier", "ampel", "keyboard"]);
+        home.write(
+            "data/pop-launcher/plugins/kicad/config.toml",
+            &format!("path = {search_path:?}"),
+        );
+        let mut plugin = KicadPlugin::new().unwrap();
+
+        let responses = drive(&mut plugin, [Request::Search("kicad amp".into())]).await;
+        assert_eq!(
+            summary(&responses),
+            ["Clear", "ampel", "amplifier", "Finished"]
+        );
+
+        let responses = drive(&mut plugin, [Request::Search("kicad board".into())]).await;
+        assert_eq!(summary(&responses), ["Clear", "keyboard", "Finished"]);
+    }
+
+    #[tokio::test]
+    async fn copies_the_project_path() {
+        let home = XdgHome::new();
+        let search_path = seed_projects(&home, &["amplifier"]);
+        let copied = home.write("copied", "");
+        home.write(
+            "data/pop-launcher/plugins/kicad/config.toml",
+            &format!(
+                "path = {search_path:?}\nclipboard = {:?}",
+                format!("sh -c 'cat > {}'", copied.display())
+            ),
+        );
+        clipboard::init(manifest::KICAD.id);
+        let mut plugin = KicadPlugin::new().unwrap();
+
+        let results = drive(&mut plugin, [Request::Search("kicad amplifier".into())]).await;
+        let id = result_id(&results, "amplifier");
+        let responses = drive(&mut plugin, [Request::Context(id)]).await;
+        let options = match responses.as_slice() {
+            [PluginResponse::Context { options, .. }] => options
+                .iter()
+                .map(|option| option.name.clone())
+                .collect::<Vec<_>>(),
+            other => panic!("Unexpected responses {other:?}"),
+        };
+        assert_eq!(options, ["Open folder", "Copy path"]);
+
+        let responses = drive(&mut plugin, [Request::ActivateContext { id, context: 1 }]).await;
+        assert_eq!(summary(&responses), ["Close"]);
+        assert_eq!(
+            read_to_string(copied).unwrap(),
+            format!("{search_path}/amplifier/amplifier.kicad_pro")
+        );
+    }
+
+    #[tokio::test]
+    async fn opens_projects_in_kicad_from_their_folder() {
+        let home = XdgHome::new();
+        let search_path = seed_projects(&home, &["amplifier"]);
+        home.write(
+            "data/pop-launcher/plugins/kicad/config.toml",
+            &format!("path = {search_path:?}"),
+        );
+        let calls = home.fake_program("kicad");
+        let mut plugin = KicadPlugin::new().unwrap();
+
+        let results = drive(&mut plugin, [Request::Search("kicad amp".into())]).await;
+        let id = result_id(&results, "amplifier");
+        let responses = drive(&mut plugin, [Request::Activate(id)]).await;
+
+        assert_eq!(summary(&responses), ["Close"]);
+        let folder = format!("{search_path}/amplifier");
+        assert_eq!(
+            read_to_string(calls).unwrap(),
+            format!("{folder}\n{folder}/amplifier.kicad_pro\n")
+        );
+    }
+
+    #[test]
+    fn failed_scans_are_errors() {
+        let home = XdgHome::new();
+        home.failing_program(
+            "fd",
+            "/projects/amplifier.kicad_pro\\n",
+            "[fd error]: Permission denied\\n",
+        );
+
+        match get_projects("/projects") {
+            Err(Error::Launch {
+                program, stderr, ..
+            }) => {
+                assert_eq!(program, "fd");
+                assert_eq!(stderr, "[fd error]: Permission denied");
+            }
+            other => panic!("Unexpected result {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! The plugins shipped by this crate. Each has its own binary, and all of them are bundled by the
+//! `host` binary.
+//!
+//! Every plugin sits behind a cargo feature of the same name, so a build can leave out the ones
+//! whose system libraries are not around.
+
+#[cfg(feature = "commando")]
+pub mod commando;
+#[cfg(feature = "kicad")]
+pub mod kicad;
+#[cfg(feature = "mpris")]
+pub mod mpris;
+#[cfg(feature = "notmuch")]
+pub mod notmuch;
+#[cfg(feature = "rust")]
+pub mod rust;
+
+use crate::{host::Host, manifest::Manifest, PopLauncherPlugin};
+
+/// Starts the plugin described by `manifest` with [`crate::start_plugin`]. Unknown plugins, and
+/// those left out of the build, are `None`.
+pub fn init(manifest: &Manifest) -> Option<Box<dyn PopLauncherPlugin>> {
+    match manifest.id {
+        #[cfg(feature = "commando")]
+        "commando" => Some(crate::start_plugin(manifest.id, || {
+            Ok(commando::CommandoPlugin::new())
+        })),
+        #[cfg(feature = "kicad")]
+        "kicad" => Some(crate::start_plugin(manifest.id, kicad::KicadPlugin::new)),
+        #[cfg(feature = "mpris")]
+        "mpris" => Some(crate::start_plugin(manifest.id, mpris::MprisPlugin::new)),
+        #[cfg(feature = "notmuch")]
+        "notmuch" => Some(crate::start_plugin(
+            manifest.id,
+            notmuch::NotmuchPlugin::new,
+        )),
+        #[cfg(feature = "rust")]
+        "rust" => Some(crate::start_plugin(manifest.id, rust::RustDocsPlugin::new)),
+        _ => None,
+    }
+}
+
+/// Starts the plugin described by `manifest` in the host. Unknown plugins, and those left out of
+/// the build, are skipped.
+pub fn start(host: Host, manifest: &Manifest) -> Host {
+    match init(manifest) {
+        Some(plugin) => host.with_plugin(plugin),
+        None => host,
+    }
+}
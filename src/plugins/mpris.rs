@@ -0,0 +1,382 @@
+//! Controls media players over MPRIS.
+
+use std::{fmt::Display, path::PathBuf, rc::Rc};
+
+use crate::{
+    clipboard, get_config_candidates,
+    history::History,
+    icon::{self, Icons},
+    launcher, manifest, notify, query,
+    ranking::{Fields, Ranker},
+    ContextActions, ContextMenu, Error, ItemStore, PopLauncherPlugin, QueryPrefix, Responder,
+    Result,
+};
+use async_trait::async_trait;
+use log::{error, info, warn};
+use pop_launcher::{IconSource, PluginResponse, PluginSearchResult};
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum PlayerControls {
+    VolumeUp,
+    VolumeDown,
+    Play,
+    Pause,
+}
+
+impl From<&PlayerControls> for &'static str {
+    fn from(val: &PlayerControls) -> Self {
+        match val {
+            PlayerControls::VolumeUp => "Volume up",
+            PlayerControls::VolumeDown => "Volume down",
+            PlayerControls::Play => "Play",
+            PlayerControls::Pause => "Pause",
+        }
+    }
+}
+
+impl TryFrom<&'static str> for PlayerControls {
+    type Error = &'static str;
+
+    fn try_from(value: &'static str) -> Result<Self, Self::Error> {
+        for control in PlayerControls::iter() {
+            let control_str: &str = (&control).into();
+            if value == control_str {
+                return Ok(control);
+            }
+        }
+
+        Err("Could not find matching control")
+    }
+}
+
+impl Display for &PlayerControls {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str((*self).into())
+    }
+}
+
+impl PlayerControls {
+    fn iter() -> impl Iterator<Item = PlayerControls> {
+        [
+            PlayerControls::VolumeUp,
+            PlayerControls::VolumeDown,
+            PlayerControls::Play,
+            PlayerControls::Pause,
+        ]
+        .into_iter()
+    }
+
+    fn get_matches(
+        ranker: &Ranker,
+        history: &History,
+        player: &mpris::Player,
+        query: &str,
+    ) -> Vec<Self> {
+        ranker.rank(query, Self::iter(), |action| {
+            Fields::new(action.into()).boost(history.frecency(&action_key(player, action)))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Action {
+    CopyTrack,
+}
+
+// Players are shared by the player's result and those of its controls
+#[derive(Debug, Clone)]
+pub enum Item {
+    Player(Rc<mpris::Player>),
+    Action(Rc<mpris::Player>, PlayerControls),
+}
+
+pub struct MprisPlugin {
+    mpris: mpris::PlayerFinder,
+    responder: Responder,
+    ranker: Ranker,
+    history: History,
+    items: ItemStore<Item, Action>,
+    query_prefix: Option<QueryPrefix>,
+    icons: Icons,
+}
+
+impl MprisPlugin {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            mpris: mpris::PlayerFinder::new().map_err(|error| {
+                Error::unavailable(format!("Could not connect to D-Bus: {error}"))
+            })?,
+            responder: Responder::default(),
+            ranker: Ranker::default(),
+            history: History::load(manifest::MPRIS.id),
+            items: ItemStore::default(),
+            query_prefix: QueryPrefix::load(&manifest::MPRIS),
+            icons: Icons::load(manifest::MPRIS.id),
+        })
+    }
+
+    fn format_player(&self, player: &mpris::Player) -> Option<PluginSearchResult> {
+        Some(PluginSearchResult {
+            id: 0,
+            name: player.identity().to_owned(),
+            description: String::from("Description"),
+            keywords: None,
+            icon: get_player_icon(&self.icons, player),
+            exec: None,
+            window: None,
+        })
+    }
+
+    fn format_action(&self, action: &PlayerControls) -> Option<PluginSearchResult> {
+        let name: &str = action.into();
+        Some(PluginSearchResult {
+            id: 0,
+            name: name.to_owned(),
+            description: String::new(),
+            keywords: None,
+            icon: get_action_icon(&self.icons, action),
+            exec: None,
+            window: None,
+        })
+    }
+
+    async fn clear(&mut self) {
+        self.items.clear(&mut self.responder).await;
+    }
+
+    async fn add_player(&mut self, item: mpris::Player) {
+        if let Some(result) = self.format_player(&item) {
+            let actions = vec![clipboard::copy_action("track", Action::CopyTrack)];
+            self.items
+                .append_with_actions(
+                    &mut self.responder,
+                    Item::Player(Rc::new(item)),
+                    actions,
+                    result,
+                )
+                .await;
+        }
+    }
+
+    async fn add_action(&mut self, player: Rc<mpris::Player>, action: PlayerControls) {
+        if let Some(result) = self.format_action(&action) {
+            self.items
+                .append(&mut self.responder, Item::Action(player, action), result)
+                .await;
+        }
+    }
+
+    fn get_player_matches(&mut self, query: &str) -> Vec<mpris::Player> {
+        let players = self.get_all_players();
+        self.ranker
+            .rank(query, players, |player| Fields::new(player.identity()))
+    }
+
+    fn get_all_players(&mut self) -> Vec<mpris::Player> {
+        match self.mpris.find_all() {
+            Ok(players) => players,
+            Err(_) => {
+                warn!("Could not find players");
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl PopLauncherPlugin for MprisPlugin {
+    async fn search(&mut self, input: &str) {
+        info!("Searching for players");
+
+        self.clear().await;
+
+        let player_option = self
+            .get_all_players()
+            .into_iter()
+            .find(|player| input.starts_with(player.identity()));
+
+        match player_option {
+            Some(player) => {
+                let input = input.strip_prefix(player.identity()).unwrap().trim_start();
+                let player = Rc::new(player);
+
+                for action in
+                    PlayerControls::get_matches(&self.ranker, &self.history, &player, input)
+                {
+                    self.add_action(player.clone(), action).await;
+                }
+            }
+            None => {
+                for player in self.get_player_matches(input) {
+                    self.add_player(player).await;
+                }
+            }
+        }
+
+        self.responder.respond(PluginResponse::Finished).await;
+    }
+
+    async fn activate(&mut self, id: u32) {
+        let item = match self.items.get(id) {
+            Some(item) => item,
+            None => {
+                error!("Could not activate item with id {id}");
+                return;
+            }
+        };
+
+        let (player, action) = match item {
+            Item::Player(_player) => {
+                self.complete(id).await;
+                return;
+            }
+            Item::Action(player, action) => (player, action),
+        };
+
+        self.history.record(&action_key(player, action));
+
+        let outcome = match action {
+            PlayerControls::VolumeUp => increase_volume(player, 0.1),
+            PlayerControls::VolumeDown => decrease_volume(player, 0.1),
+            PlayerControls::Play => play(player),
+            PlayerControls::Pause => pause(player),
+        };
+
+        match outcome {
+            Ok(()) => notify::success(player.identity(), &action.to_string()),
+            Err(error) => {
+                error!("{action} failed for {}: {error}", player.identity());
+                notify::failure(&format!("{action} failed"), &error.to_string());
+            }
+        }
+
+        // Volume changes keep the launcher open, so they can be repeated
+        if matches!(action, PlayerControls::Play | PlayerControls::Pause) {
+            self.responder.respond(PluginResponse::Close).await;
+        }
+    }
+
+    async fn complete(&mut self, id: u32) {
+        let query = match self.items.get(id) {
+            Some(Item::Player(player)) => format!("{} ", player.identity()),
+            Some(Item::Action(player, action)) => format!("{} {}", player.identity(), action),
+            None => {
+                warn!("Item does not exist");
+                return;
+            }
+        };
+        query::fill(&mut self.responder, self.query_prefix.as_ref(), &query).await;
+        self.search(&query).await;
+    }
+
+    fn context_menu(&mut self) -> Option<&mut dyn ContextMenu> {
+        Some(self)
+    }
+
+    fn responder(&mut self) -> &mut Responder {
+        &mut self.responder
+    }
+
+    fn query_prefix(&self) -> Option<&QueryPrefix> {
+        self.query_prefix.as_ref()
+    }
+
+    fn watched_paths(&self) -> Vec<PathBuf> {
+        get_config_candidates(manifest::MPRIS.id).unwrap_or_default()
+    }
+
+    async fn reload(&mut self) {
+        self.query_prefix = QueryPrefix::load(&manifest::MPRIS);
+        self.icons = Icons::load(manifest::MPRIS.id);
+        self.responder.load_limit(manifest::MPRIS.id);
+    }
+}
+
+#[async_trait(?Send)]
+impl ContextActions for MprisPlugin {
+    type Item = Item;
+    type Action = Action;
+
+    fn items(&self) -> &ItemStore<Item, Action> {
+        &self.items
+    }
+
+    async fn handle_action(&mut self, item: Item, action: Action) {
+        let player = match item {
+            Item::Player(player) => player,
+            Item::Action(..) => return,
+        };
+
+        match action {
+            Action::CopyTrack => match now_playing(&player) {
+                Ok(track) => clipboard::finish(&mut self.responder, &track).await,
+                Err(error) => {
+                    let outcome = Err(Error::unavailable(error));
+                    launcher::report(&mut self.responder, outcome, "Could not copy").await;
+                }
+            },
+        }
+    }
+}
+
+fn action_key(player: &mpris::Player, action: &PlayerControls) -> String {
+    format!("{} {}", player.identity(), action)
+}
+
+// "Artist - Title", or just the title if the player does not know the artists
+fn now_playing(player: &mpris::Player) -> anyhow::Result<String> {
+    let metadata = player.get_metadata()?;
+    let title = metadata
+        .title()
+        .ok_or_else(|| anyhow::anyhow!("{} reports no title", player.identity()))?;
+
+    Ok(match metadata.artists() {
+        Some(artists) if !artists.is_empty() => format!("{} - {title}", artists.join(", ")),
+        _ => title.to_owned(),
+    })
+}
+
+fn increase_volume(player: &mpris::Player, increase: f64) -> anyhow::Result<()> {
+    let current_volume = player.get_volume()?;
+    player.set_volume(current_volume + increase)?;
+    Ok(())
+}
+
+fn decrease_volume(player: &mpris::Player, decrease: f64) -> anyhow::Result<()> {
+    let current_volume = player.get_volume()?;
+    if decrease > current_volume {
+        player.set_volume(0f64)?;
+    } else {
+        player.set_volume(current_volume - decrease)?;
+    }
+    Ok(())
+}
+
+fn play(player: &mpris::Player) -> anyhow::Result<()> {
+    player.play()?;
+    Ok(())
+}
+
+fn pause(player: &mpris::Player) -> anyhow::Result<()> {
+    player.pause()?;
+    Ok(())
+}
+
+fn get_player_icon(icons: &Icons, player: &mpris::Player) -> Option<IconSource> {
+    icons
+        .configured(player.identity())
+        .or_else(|| {
+            let entry = player.get_desktop_entry().ok().flatten()?;
+            icon::for_desktop_entry(&entry)
+        })
+        .or_else(|| icons.get("player", &["multimedia-player", "folder-music"]))
+}
+
+fn get_action_icon(icons: &Icons, action: &PlayerControls) -> Option<IconSource> {
+    match action {
+        PlayerControls::VolumeUp => icons.get("volume-up", &["audio-volume-high"]),
+        PlayerControls::VolumeDown => icons.get("volume-down", &["audio-volume-low"]),
+        PlayerControls::Play => icons.get("play", &["media-playback-start", "player_play"]),
+        PlayerControls::Pause => icons.get("pause", &["media-playback-pause", "player_pause"]),
+    }
+}
@@ -0,0 +1,255 @@
+//! Searches mail threads in the notmuch database.
+
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use blocking::unblock;
+use pop_launcher::{PluginResponse, PluginSearchResult};
+
+use crate::{
+    clipboard, get_config_candidates,
+    icon::Icons,
+    launcher::{self, Launch},
+    manifest,
+    query::{self, Completion, FilterKey, Query, QueryParser},
+    ContextActions, ContextMenu, Error, ItemStore, PopLauncherPlugin, QueryPrefix, Responder,
+    Result,
+};
+use log::{info, warn};
+
+fn open_database() -> Result<notmuch::Database> {
+    let database = notmuch::Database::open_with_config(
+        Some(Path::new("/home/daniel/mail/")),
+        notmuch::DatabaseMode::ReadOnly,
+        Some(Path::new("/home/daniel/.config/notmuch/notmuchrc")),
+        None,
+    )
+    .map_err(|error| Error::unavailable(format!("Could not open notmuch database: {error}")))?;
+    Ok(database)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Action {
+    CopyThreadId,
+}
+
+// What a search keeps of a `notmuch::Thread`, which cannot leave the thread that searched
+#[derive(Clone)]
+pub struct Thread {
+    id: String,
+    subject: String,
+}
+
+#[derive(Clone)]
+pub enum Item {
+    Thread(Thread),
+    Filter(Completion),
+}
+
+pub struct NotmuchPlugin {
+    responder: Responder,
+    items: ItemStore<Item, Action>,
+    parser: QueryParser,
+    query_prefix: Option<QueryPrefix>,
+    icons: Icons,
+}
+
+impl NotmuchPlugin {
+    pub fn new() -> Result<Self> {
+        let database = open_database()?;
+        info!("Loaded notmuch database");
+        let tags = match database.all_tags() {
+            Ok(tags) => tags.collect(),
+            Err(error) => {
+                warn!("Could not list tags: {error}");
+                Vec::new()
+            }
+        };
+
+        Ok(Self {
+            parser: QueryParser::new(vec![
+                FilterKey::new("tag", "Threads with this tag").values(tags),
+                FilterKey::new("from", "Threads with mail from this sender"),
+                FilterKey::new("to", "Threads with mail to this recipient"),
+                FilterKey::new("subject", "Threads with this in the subject"),
+            ]),
+            responder: Responder::default(),
+            items: ItemStore::default(),
+            query_prefix: QueryPrefix::load(&manifest::NOTMUCH),
+            icons: Icons::load(manifest::NOTMUCH.id),
+        })
+    }
+
+    fn format_item(&self, item: &Thread) -> Option<PluginSearchResult> {
+        Some(PluginSearchResult {
+            id: 0,
+            name: item.subject.clone(),
+            description: String::from("Description"),
+            keywords: None,
+            icon: self
+                .icons
+                .get("thread", &["mail-read", "mail-message", "internet-mail"]),
+            exec: None,
+            window: None,
+        })
+    }
+
+    async fn clear(&mut self) {
+        self.items.clear(&mut self.responder).await;
+    }
+
+    async fn add_item(&mut self, item: Thread) {
+        if let Some(result) = self.format_item(&item) {
+            let actions = vec![clipboard::copy_action("thread id", Action::CopyThreadId)];
+            self.items
+                .append_with_actions(&mut self.responder, Item::Thread(item), actions, result)
+                .await;
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl PopLauncherPlugin for NotmuchPlugin {
+    async fn search(&mut self, input: &str) {
+        info!("Received request with query {input}");
+        let query = to_notmuch(&self.parser.parse(input));
+        // Searching a large database takes a while, so other requests are read meanwhile
+        let threads = match unblock(move || search_threads(&query)).await {
+            Ok(threads) => threads,
+            Err(error) => {
+                warn!("{error}");
+                self.responder.respond(PluginResponse::Finished).await;
+                return;
+            }
+        };
+
+        self.clear().await;
+        for completion in self.parser.completions(input) {
+            let result = completion.result();
+            self.items
+                .append(&mut self.responder, Item::Filter(completion), result)
+                .await;
+        }
+        for thread in threads {
+            self.add_item(thread).await;
+        }
+
+        self.responder.respond(PluginResponse::Finished).await;
+    }
+
+    async fn activate(&mut self, id: u32) {
+        let item = match self.items.get(id) {
+            Some(Item::Thread(item)) => item,
+            Some(Item::Filter(_)) => return self.complete(id).await,
+            None => return,
+        };
+        let id = &item.id;
+        info!("Received activate request");
+        let outcome = Launch::open(format!("notmuch://thread/{id}")).spawn().await;
+        launcher::finish(&mut self.responder, outcome).await;
+    }
+
+    async fn complete(&mut self, id: u32) {
+        let query = match self.items.get(id) {
+            Some(Item::Filter(completion)) => completion.query.clone(),
+            _ => return,
+        };
+
+        query::fill(&mut self.responder, self.query_prefix.as_ref(), &query).await;
+        self.search(&query).await;
+    }
+
+    fn context_menu(&mut self) -> Option<&mut dyn ContextMenu> {
+        Some(self)
+    }
+
+    fn responder(&mut self) -> &mut Responder {
+        &mut self.responder
+    }
+
+    fn query_prefix(&self) -> Option<&QueryPrefix> {
+        self.query_prefix.as_ref()
+    }
+
+    fn watched_paths(&self) -> Vec<PathBuf> {
+        get_config_candidates(manifest::NOTMUCH.id).unwrap_or_default()
+    }
+
+    async fn reload(&mut self) {
+        self.query_prefix = QueryPrefix::load(&manifest::NOTMUCH);
+        self.icons = Icons::load(manifest::NOTMUCH.id);
+        self.responder.load_limit(manifest::NOTMUCH.id);
+    }
+}
+
+#[async_trait(?Send)]
+impl ContextActions for NotmuchPlugin {
+    type Item = Item;
+    type Action = Action;
+
+    fn items(&self) -> &ItemStore<Item, Action> {
+        &self.items
+    }
+
+    async fn handle_action(&mut self, item: Item, action: Action) {
+        let item = match item {
+            Item::Thread(item) => item,
+            Item::Filter(_) => return,
+        };
+
+        match action {
+            // In the form notmuch search terms take it
+            Action::CopyThreadId => {
+                let text = format!("thread:{}", item.id);
+                clipboard::finish(&mut self.responder, &text).await;
+            }
+        }
+    }
+}
+
+// The database is opened again for every search, on a blocking thread, since notmuch's handles
+// cannot be sent to another thread. It also means new mail shows up without a restart.
+fn search_threads(query: &str) -> Result<Vec<Thread>> {
+    let database = open_database()?;
+    let query = database.create_query(query).map_err(|error| {
+        Error::unavailable(format!("Could not query notmuch database: {error}"))
+    })?;
+    let threads = query
+        .search_threads()
+        .map_err(|error| Error::unavailable(format!("Could not search threads: {error}")))?;
+
+    // All of them, the responder shows the first `max_results` and offers the rest on demand
+    Ok(threads
+        .map(|thread| Thread {
+            id: thread.id().to_owned(),
+            subject: thread.subject().into_owned(),
+        })
+        .collect())
+}
+
+// Filters and phrases in notmuch's syntax. Words are passed on as they are, so anything else
+// notmuch understands, like `date:yesterday..` or `or`, still works.
+fn to_notmuch(query: &Query) -> String {
+    let terms = query.terms.iter().map(|term| quote(term));
+    let excluded = query
+        .excluded
+        .iter()
+        .map(|word| format!("not {}", quote(word)));
+    let filters = query.filters.iter().map(|filter| {
+        let not = if filter.negated { "not " } else { "" };
+        format!("{not}{}:{}", filter.key, quote(&filter.value))
+    });
+
+    let parts: Vec<String> = terms.chain(excluded).chain(filters).collect();
+    match parts.is_empty() {
+        true => String::from("*"),
+        false => parts.join(" "),
+    }
+}
+
+fn quote(text: &str) -> String {
+    match text.contains(char::is_whitespace) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => text.to_owned(),
+    }
+}
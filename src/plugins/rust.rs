@@ -0,0 +1,553 @@
+//! Searches the Rust documentation installed by rustup.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    cache::Cache,
+    clipboard, get_config_candidates,
+    history::History,
+    icon::Icons,
+    launcher::{self, Launch},
+    manifest,
+    query::{self, Completion, FilterKey, QueryParser},
+    ranking::{Fields, Ranker},
+    ContextActions, ContextMenu, Error, ItemStore, PopLauncherPlugin, QueryPrefix, Responder,
+    Result,
+};
+use async_trait::async_trait;
+use log::{info, warn};
+use pop_launcher::{PluginResponse, PluginSearchResult};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    name: String,
+    r#type: Type,
+    /// Path of the containing module, e.g. `std::collections`. Empty for crates.
+    module: String,
+    file_path: PathBuf,
+}
+
+impl Entry {
+    fn key(&self) -> String {
+        self.file_path.to_string_lossy().into_owned()
+    }
+
+    /// The path to use the item in code, e.g. `std::collections::HashMap`.
+    fn path(&self) -> String {
+        match self.module.as_str() {
+            "" => self.name.clone(),
+            module => format!("{module}::{}", self.name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Action {
+    CopyPath,
+}
+
+#[derive(Debug, Clone)]
+pub enum Item {
+    Entry(Entry),
+    Filter(Completion),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Type {
+    Constant,
+    Enum,
+    Function,
+    Keyword,
+    Macro,
+    Module,
+    Primitive,
+    Struct,
+    Trait,
+    /// A type alias.
+    Alias,
+}
+
+impl Type {
+    const ALL: [Type; 10] = [
+        Type::Constant,
+        Type::Enum,
+        Type::Function,
+        Type::Keyword,
+        Type::Macro,
+        Type::Module,
+        Type::Primitive,
+        Type::Struct,
+        Type::Trait,
+        Type::Alias,
+    ];
+}
+
+impl TryFrom<&str> for Type {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "fn" => Ok(Type::Function),
+            "struct" => Ok(Type::Struct),
+            "constant" => Ok(Type::Constant),
+            "trait" => Ok(Type::Trait),
+            "macro" => Ok(Type::Macro),
+            "type" => Ok(Type::Alias),
+            "enum" => Ok(Type::Enum),
+            "primitive" => Ok(Type::Primitive),
+            "keyword" => Ok(Type::Keyword),
+            _ => Err(format!("Could not find type {value}")),
+        }
+    }
+}
+
+impl From<Type> for String {
+    fn from(val: Type) -> Self {
+        match val {
+            Type::Function => "function",
+            Type::Struct => "struct",
+            Type::Keyword => "keyword",
+            Type::Macro => "macro",
+            Type::Trait => "trait",
+            Type::Module => "module",
+            Type::Primitive => "primitive",
+            Type::Constant => "constant",
+            Type::Enum => "enum",
+            Type::Alias => "type",
+        }
+        .to_owned()
+    }
+}
+
+pub struct RustDocsPlugin {
+    index: Cache<Vec<Entry>>,
+    query_prefix: Option<QueryPrefix>,
+    icons: Icons,
+    parser: QueryParser,
+    items: ItemStore<Item, Action>,
+    responder: Responder,
+    ranker: Ranker,
+    history: History,
+}
+
+impl RustDocsPlugin {
+    pub fn new() -> Result<Self> {
+        // Installing or updating a toolchain touches its directory in here
+        let mut index = Cache::load(manifest::RUST.id, "index", vec![toolchains_dir()]);
+        index.ensure(build_index)?;
+
+        Ok(Self {
+            index,
+            query_prefix: QueryPrefix::load(&manifest::RUST),
+            icons: Icons::load(manifest::RUST.id),
+            parser: QueryParser::new(vec![FilterKey::new("type", "Only items of this kind")
+                .values(Type::ALL.map(String::from))]),
+            items: ItemStore::default(),
+            responder: Responder::default(),
+            ranker: Ranker::default(),
+            history: History::load(manifest::RUST.id),
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl PopLauncherPlugin for RustDocsPlugin {
+    async fn search(&mut self, input: &str) {
+        self.items.clear(&mut self.responder).await;
+        info!("Starting search with pattern: {input}");
+
+        self.index.refresh(build_index).await;
+
+        for completion in self.parser.completions(input) {
+            let result = completion.result();
+            self.items
+                .append(&mut self.responder, Item::Filter(completion), result)
+                .await;
+        }
+
+        let query = self.parser.parse(input);
+        let text = query.text();
+        let (module, search_term) = text.rsplit_once("::").unwrap_or(("", &text));
+        let entries = self
+            .index
+            .get()
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.module == module)
+            .filter(|entry| query.allows("type", &String::from(entry.r#type.clone())))
+            .filter(|entry| !query.excludes(&entry.name));
+
+        let matches = self.ranker.rank(search_term, entries, |entry| {
+            Fields::new(&entry.name).boost(self.history.frecency(&entry.key()))
+        });
+
+        for entry in matches {
+            let description: String = entry.r#type.clone().into();
+            let result = PluginSearchResult {
+                name: entry.name.clone(),
+                icon: self
+                    .icons
+                    .get(&description, &["text-x-rust", "rust", "text-html"]),
+                description,
+                ..Default::default()
+            };
+            let actions = vec![clipboard::copy_action("path", Action::CopyPath)];
+            self.items
+                .append_with_actions(
+                    &mut self.responder,
+                    Item::Entry(entry.clone()),
+                    actions,
+                    result,
+                )
+                .await;
+        }
+
+        self.responder.respond(PluginResponse::Finished).await;
+    }
+
+    async fn activate(&mut self, id: u32) {
+        let item = match self.items.get(id) {
+            Some(Item::Entry(item)) => item,
+            Some(Item::Filter(_)) => return self.complete(id).await,
+            None => {
+                warn!("Could not activate item with id {id}");
+                return;
+            }
+        };
+
+        info!("Activating {item:?}");
+        self.history.record(&item.key());
+        let outcome = Launch::open(&item.file_path).spawn().await;
+        launcher::finish(&mut self.responder, outcome).await;
+    }
+
+    async fn complete(&mut self, id: u32) {
+        let query = match self.items.get(id) {
+            Some(Item::Filter(completion)) => completion.query.clone(),
+            // Modules complete to their contents
+            Some(Item::Entry(entry)) => match entry.r#type {
+                Type::Module => format!("{}::", entry.path()),
+                _ => entry.path(),
+            },
+            None => return,
+        };
+
+        query::fill(&mut self.responder, self.query_prefix.as_ref(), &query).await;
+        self.search(&query).await;
+    }
+
+    fn context_menu(&mut self) -> Option<&mut dyn ContextMenu> {
+        Some(self)
+    }
+
+    fn responder(&mut self) -> &mut Responder {
+        &mut self.responder
+    }
+
+    fn query_prefix(&self) -> Option<&QueryPrefix> {
+        self.query_prefix.as_ref()
+    }
+
+    fn watched_paths(&self) -> Vec<PathBuf> {
+        get_config_candidates(manifest::RUST.id).unwrap_or_default()
+    }
+
+    async fn reload(&mut self) {
+        self.query_prefix = QueryPrefix::load(&manifest::RUST);
+        self.icons = Icons::load(manifest::RUST.id);
+        self.responder.load_limit(manifest::RUST.id);
+    }
+}
+
+#[async_trait(?Send)]
+impl ContextActions for RustDocsPlugin {
+    type Item = Item;
+    type Action = Action;
+
+    fn items(&self) -> &ItemStore<Item, Action> {
+        &self.items
+    }
+
+    async fn handle_action(&mut self, item: Item, action: Action) {
+        let item = match item {
+            Item::Entry(item) => item,
+            Item::Filter(_) => return,
+        };
+
+        info!("Activating {action:?} on {item:?}");
+        match action {
+            Action::CopyPath => clipboard::finish(&mut self.responder, &item.path()).await,
+        }
+    }
+}
+
+fn get_doc_dir() -> Result<PathBuf> {
+    let output = Command::new("rustup")
+        .arg("doc")
+        .arg("--path")
+        .output()
+        .map_err(|error| Error::spawn("rustup", error))?;
+    let index_path = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim_end());
+
+    match index_path.parent() {
+        Some(doc_dir) if !doc_dir.as_os_str().is_empty() => Ok(doc_dir.to_owned()),
+        _ => Err(Error::unavailable(
+            "rustup did not report a documentation path",
+        )),
+    }
+}
+
+fn toolchains_dir() -> PathBuf {
+    env::var_os("RUSTUP_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home::home_dir().map(|home| home.join(".rustup")))
+        .unwrap_or_default()
+        .join("toolchains")
+}
+
+fn build_index() -> Result<Vec<Entry>> {
+    let doc_dir = get_doc_dir()?;
+    info!("Indexing {doc_dir:?}");
+
+    let mut entries = Vec::new();
+    index_dir(&doc_dir, "", &mut entries);
+    Ok(entries)
+}
+
+fn index_dir(dir: &Path, module: &str, entries: &mut Vec<Entry>) {
+    let dir_entries = match dir.read_dir() {
+        Ok(dir_entries) => dir_entries,
+        Err(error) => {
+            warn!("Could not index {dir:?}: {error}");
+            return;
+        }
+    };
+
+    for dir_entry in dir_entries.filter_map(|x| x.ok()) {
+        let path = dir_entry.path();
+        let filename = match dir_entry.file_name().into_string() {
+            Ok(filename) => filename,
+            Err(_) => continue,
+        };
+
+        // Only modules have an index, unlike sources, implementors and the like
+        if path.join("index.html").is_file() {
+            let submodule = match module {
+                "" => filename.clone(),
+                module => format!("{module}::{filename}"),
+            };
+            index_dir(&path, &submodule, entries);
+
+            entries.push(Entry {
+                name: filename,
+                r#type: Type::Module,
+                module: module.to_owned(),
+                file_path: path.join("index.html"),
+            });
+            continue;
+        }
+
+        let mut file_segments = filename.split('.');
+        let item_type = file_segments
+            .next()
+            .and_then(|item_type| item_type.try_into().ok());
+        if let (Some(item_type), Some(name)) = (item_type, file_segments.next()) {
+            entries.push(Entry {
+                name: name.to_owned(),
+                r#type: item_type,
+                module: module.to_owned(),
+                file_path: path,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, fs::read_to_string};
+
+    use pop_launcher::Request;
+
+    use super::*;
+    use crate::testing::{drive, result_id, summary, XdgHome};
+
+    const PAGES: &[&str] = &[
+        "std/index.html",
+        "std/macro.vec.html",
+        "std/collections/index.html",
+        "std/collections/struct.BTreeMap.html",
+        "std/collections/struct.HashMap.html",
+        "std/collections/hash_map/index.html",
+        "std/collections/hash_map/enum.Entry.html",
+        "std/io/index.html",
+        "std/io/enum.ErrorKind.html",
+        "std/io/struct.Error.html",
+        "std/io/trait.Read.html",
+        "std/io/type.Result.html",
+        // Neither modules nor items
+        "std/io/sidebar-items.js",
+        "src/std/lib.rs.html",
+    ];
+
+    // Points `RUSTUP_HOME` into the home while it lives
+    struct Docs {
+        home: XdgHome,
+        rustup_home: Option<OsString>,
+    }
+
+    impl Drop for Docs {
+        fn drop(&mut self) {
+            match self.rustup_home.take() {
+                Some(value) => env::set_var("RUSTUP_HOME", value),
+                None => env::remove_var("RUSTUP_HOME"),
+            }
+        }
+    }
+
+    // `rustup` need not be installed, so the fixture is indexed the way `build_index` would and
+    // stored where the plugin looks for its index
+    fn docs() -> Docs {
+        let home = XdgHome::new();
+        let rustup_home = env::var_os("RUSTUP_HOME");
+        let toolchains = home.write("rustup/toolchains/.keep", "");
+        env::set_var(
+            "RUSTUP_HOME",
+            toolchains.parent().unwrap().parent().unwrap(),
+        );
+
+        let doc_dir = home.write("doc/.keep", "");
+        let doc_dir = doc_dir.parent().unwrap().to_owned();
+        for page in PAGES {
+            home.write(doc_dir.join(page), "");
+        }
+
+        Cache::load(manifest::RUST.id, "index", vec![toolchains_dir()])
+            .ensure(|| {
+                let mut entries = Vec::new();
+                index_dir(&doc_dir, "", &mut entries);
+                Ok(entries)
+            })
+            .unwrap();
+
+        Docs { home, rustup_home }
+    }
+
+    #[tokio::test]
+    async fn searches_within_the_module() {
+        let _docs = docs();
+        let mut plugin = RustDocsPlugin::new().unwrap();
+
+        let responses = drive(&mut plugin, [Request::Search("rust std::io::Err".into())]).await;
+        assert_eq!(
+            summary(&responses),
+            ["Clear", "Error", "ErrorKind", "Finished"]
+        );
+
+        let responses = drive(&mut plugin, [Request::Search("rust Map".into())]).await;
+        assert_eq!(summary(&responses), ["Clear", "Finished"]);
+
+        let responses = drive(&mut plugin, [Request::Search("rust st".into())]).await;
+        assert_eq!(summary(&responses), ["Clear", "std", "Finished"]);
+    }
+
+    #[tokio::test]
+    async fn filters_by_type_and_excluded_words() {
+        let _docs = docs();
+        let mut plugin = RustDocsPlugin::new().unwrap();
+
+        let responses = drive(
+            &mut plugin,
+            [Request::Search("rust std::io:: type:type".into())],
+        )
+        .await;
+        assert_eq!(summary(&responses), ["Clear", "Result", "Finished"]);
+
+        let responses = drive(
+            &mut plugin,
+            [Request::Search(
+                "rust std::io:: -type:struct -type:trait -kind".into(),
+            )],
+        )
+        .await;
+        assert_eq!(summary(&responses), ["Clear", "Result", "Finished"]);
+    }
+
+    #[tokio::test]
+    async fn completes_filters_and_modules() {
+        let _docs = docs();
+        let mut plugin = RustDocsPlugin::new().unwrap();
+
+        let responses = drive(&mut plugin, [Request::Search("rust ty".into())]).await;
+        assert_eq!(summary(&responses), ["Clear", "type:", "Finished"]);
+
+        let id = result_id(&responses, "type:");
+        let responses = drive(&mut plugin, [Request::Complete(id)]).await;
+        assert_eq!(summary(&responses[..1]), ["Fill(\"rust type:\")"]);
+
+        let responses = drive(&mut plugin, [Request::Search("rust std::coll".into())]).await;
+        assert_eq!(summary(&responses), ["Clear", "collections", "Finished"]);
+
+        let id = result_id(&responses, "collections");
+        let responses = drive(&mut plugin, [Request::Complete(id)]).await;
+        let mut names = summary(&responses);
+        names[2..5].sort();
+        assert_eq!(
+            names,
+            [
+                "Fill(\"rust std::collections::\")",
+                "Clear",
+                "BTreeMap",
+                "HashMap",
+                "hash_map",
+                "Finished"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn copies_the_path_to_use_the_item() {
+        let docs = docs();
+        let copied = docs.home.write("copied", "");
+        docs.home.write(
+            "data/pop-launcher/plugins/rust/config.toml",
+            &format!(
+                "clipboard = {:?}",
+                format!("sh -c 'cat > {}'", copied.display())
+            ),
+        );
+        clipboard::init(manifest::RUST.id);
+        let mut plugin = RustDocsPlugin::new().unwrap();
+
+        let results = drive(
+            &mut plugin,
+            [Request::Search("rust std::collections::HashMap".into())],
+        )
+        .await;
+        let id = result_id(&results, "HashMap");
+        let responses = drive(&mut plugin, [Request::ActivateContext { id, context: 0 }]).await;
+
+        assert_eq!(summary(&responses), ["Close"]);
+        assert_eq!(read_to_string(copied).unwrap(), "std::collections::HashMap");
+    }
+
+    #[tokio::test]
+    async fn opens_the_documentation_page() {
+        let docs = docs();
+        let calls = docs.home.fake_program("xdg-open");
+        let mut plugin = RustDocsPlugin::new().unwrap();
+
+        let results = drive(&mut plugin, [Request::Search("rust std::io::Read".into())]).await;
+        let id = result_id(&results, "Read");
+        let responses = drive(&mut plugin, [Request::Activate(id)]).await;
+
+        assert_eq!(summary(&responses), ["Close"]);
+        let calls = read_to_string(calls).unwrap();
+        let page = calls.lines().nth(1).unwrap();
+        assert!(page.ends_with("/doc/std/io/trait.Read.html"), "{calls}");
+    }
+}
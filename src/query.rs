@@ -0,0 +1,393 @@
+//! Splitting queries into free text and `key:value` filters.
+//!
+//! `type:trait -type:macro "hash map" -deprecated` has two filters, the phrase `hash map` and the
+//! excluded word `deprecated`. Only keys the plugin declares are filters, anything else with a
+//! colon in it, like `std::collections`, stays text.
+
+use pop_launcher::{IconSource, PluginResponse, PluginSearchResult};
+
+use crate::{QueryPrefix, Responder};
+
+/// A filter a plugin understands, with the values to suggest for it, if there is a fixed set.
+#[derive(Debug, Clone)]
+pub struct FilterKey {
+    pub name: String,
+    pub description: String,
+    pub values: Vec<String>,
+}
+
+impl FilterKey {
+    pub fn new(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            values: Vec::new(),
+        }
+    }
+
+    pub fn values<I, S>(mut self, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.values = values.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    pub key: String,
+    pub value: String,
+    /// Written as `-key:value`, asking for results that do not match.
+    pub negated: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    /// Words and phrases that are not filters, in order.
+    pub terms: Vec<String>,
+    /// Words and phrases written with a leading `-`.
+    pub excluded: Vec<String>,
+    pub filters: Vec<Filter>,
+}
+
+impl Query {
+    /// The free text, phrases included, separated by spaces.
+    pub fn text(&self) -> String {
+        self.terms.join(" ")
+    }
+
+    /// Whether `value` passes the filters for `key`: it has to be one of the values asked for, if
+    /// any are, and none of the negated ones. Compared ignoring case.
+    pub fn allows(&self, key: &str, value: &str) -> bool {
+        let (negated, requested): (Vec<&Filter>, Vec<&Filter>) = self
+            .filters
+            .iter()
+            .filter(|filter| filter.key == key)
+            .partition(|filter| filter.negated);
+        let matches = |filter: &&Filter| filter.value.eq_ignore_ascii_case(value);
+
+        (requested.is_empty() || requested.iter().any(matches)) && !negated.iter().any(matches)
+    }
+
+    /// Whether `text` contains any of the excluded words, ignoring case.
+    pub fn excludes(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        self.excluded
+            .iter()
+            .any(|word| text.contains(&word.to_lowercase()))
+    }
+}
+
+/// A query completed up to a filter key or value, offered as a result of its own.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    /// The whole query once completed, without the plugin's prefix.
+    pub query: String,
+    pub name: String,
+    pub description: String,
+}
+
+impl Completion {
+    pub fn result(&self) -> PluginSearchResult {
+        PluginSearchResult {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            icon: Some(IconSource::Name("edit-find".into())),
+            ..Default::default()
+        }
+    }
+}
+
+/// Puts `query` into the launcher's search field, behind the plugin's prefix.
+pub async fn fill(responder: &mut Responder, prefix: Option<&QueryPrefix>, query: &str) {
+    let query = match prefix {
+        Some(prefix) => format!("{} {query}", prefix.primary()),
+        None => query.to_owned(),
+    };
+    responder.respond(PluginResponse::Fill(query)).await;
+}
+
+/// Parses queries with the filter keys of a plugin.
+#[derive(Debug, Clone, Default)]
+pub struct QueryParser {
+    keys: Vec<FilterKey>,
+}
+
+impl QueryParser {
+    pub fn new(keys: Vec<FilterKey>) -> Self {
+        Self { keys }
+    }
+
+    pub fn parse(&self, input: &str) -> Query {
+        let mut query = Query::default();
+
+        for token in tokenize(input) {
+            match self.split(&token) {
+                Some((negated, key, value)) if !value.is_empty() => query.filters.push(Filter {
+                    key: key.to_owned(),
+                    value: value.to_owned(),
+                    negated,
+                }),
+                // A key still being typed does not filter anything yet
+                Some(_) => (),
+                None => match token.negated() {
+                    Some(word) if !word.is_empty() => query.excluded.push(word.to_owned()),
+                    _ => query.terms.push(token.text),
+                },
+            }
+        }
+
+        query
+    }
+
+    /// Completions for the word the user is typing: filter keys starting with it, or the values
+    /// of a key starting with what follows the colon.
+    pub fn completions(&self, input: &str) -> Vec<Completion> {
+        // Neither a finished word nor one with quotes is completed
+        let token = match tokenize(input).pop() {
+            Some(token) if token.end == input.len() && token.quoted_from.is_none() => token,
+            _ => return Vec::new(),
+        };
+
+        let before = &input[..token.start];
+
+        if let Some((negated, key, value)) = self.split(&token) {
+            let negated = if negated { "-" } else { "" };
+            let value = value.to_lowercase();
+            let filter_key = self.keys.iter().find(|filter_key| filter_key.name == key);
+
+            return filter_key
+                .into_iter()
+                .flat_map(|filter_key| &filter_key.values)
+                .filter(|candidate| {
+                    let candidate = candidate.to_lowercase();
+                    candidate.starts_with(&value) && candidate != value
+                })
+                .map(|candidate| Completion {
+                    query: format!("{before}{negated}{key}:{} ", quote(candidate)),
+                    name: format!("{negated}{key}:{candidate}"),
+                    description: filter_key
+                        .map(|k| k.description.clone())
+                        .unwrap_or_default(),
+                })
+                .collect();
+        }
+
+        let (negated, word) = match token.negated() {
+            Some(word) => ("-", word),
+            None => ("", token.text.as_str()),
+        };
+        if word.is_empty() {
+            return Vec::new();
+        }
+
+        self.keys
+            .iter()
+            .filter(|filter_key| filter_key.name.starts_with(word))
+            .map(|filter_key| Completion {
+                query: format!("{before}{negated}{}:", filter_key.name),
+                name: format!("{negated}{}:", filter_key.name),
+                description: filter_key.description.clone(),
+            })
+            .collect()
+    }
+
+    // `(negated, key, value)` if the token is a filter with one of our keys
+    fn split<'a>(&self, token: &'a Token) -> Option<(bool, &'a str, &'a str)> {
+        let (negated, text, offset) = match token.negated() {
+            Some(rest) => (true, rest, 1),
+            None => (false, token.text.as_str(), 0),
+        };
+
+        let colon = text.find(':')?;
+        // The colon has to come before any quotes, `"a:b"` is a phrase
+        if token
+            .quoted_from
+            .is_some_and(|quoted| quoted < colon + offset)
+        {
+            return None;
+        }
+
+        let key = &text[..colon];
+        self.keys
+            .iter()
+            .any(|filter_key| filter_key.name == key)
+            .then(|| (negated, key, &text[colon + 1..]))
+    }
+}
+
+#[derive(Debug)]
+struct Token {
+    // Without the quotes
+    text: String,
+    // Position in `text` of the first quoted character
+    quoted_from: Option<usize>,
+    // Byte range in the input
+    start: usize,
+    end: usize,
+}
+
+impl Token {
+    // The rest of a token written with a leading `-`
+    fn negated(&self) -> Option<&str> {
+        match self.quoted_from {
+            Some(0) => None,
+            _ => self.text.strip_prefix('-'),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
+    let mut quoted = false;
+
+    for (index, char) in input.char_indices() {
+        if char.is_whitespace() && !quoted {
+            tokens.extend(current.take());
+            continue;
+        }
+
+        let token = current.get_or_insert_with(|| Token {
+            text: String::new(),
+            quoted_from: None,
+            start: index,
+            end: index,
+        });
+        token.end = index + char.len_utf8();
+
+        if char == '"' {
+            quoted = !quoted;
+            if quoted && token.quoted_from.is_none() {
+                token.quoted_from = Some(token.text.len());
+            }
+        } else {
+            token.text.push(char);
+        }
+    }
+    tokens.extend(current);
+    tokens
+}
+
+fn quote(value: &str) -> String {
+    match value.contains(char::is_whitespace) {
+        true => format!("\"{value}\""),
+        false => value.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser() -> QueryParser {
+        QueryParser::new(vec![
+            FilterKey::new("type", "Only items of this kind").values([
+                "struct",
+                "trait",
+                "type alias",
+            ]),
+            FilterKey::new("tag", "Tagged with"),
+        ])
+    }
+
+    fn filter(key: &str, value: &str, negated: bool) -> Filter {
+        Filter {
+            key: key.to_owned(),
+            value: value.to_owned(),
+            negated,
+        }
+    }
+
+    fn names(completions: Vec<Completion>) -> Vec<String> {
+        completions
+            .into_iter()
+            .map(|completion| completion.name)
+            .collect()
+    }
+
+    #[test]
+    fn only_declared_keys_are_filters() {
+        let query = parser().parse("std::collections::Hash type:struct");
+        assert_eq!(query.terms, ["std::collections::Hash"]);
+        assert_eq!(query.filters, [filter("type", "struct", false)]);
+        assert_eq!(query.text(), "std::collections::Hash");
+    }
+
+    #[test]
+    fn quotes_group_phrases_and_values() {
+        let query = parser().parse("tag:\"a b\" c \"hash map\"");
+        assert_eq!(query.filters, [filter("tag", "a b", false)]);
+        assert_eq!(query.terms, ["c", "hash map"]);
+
+        // Quoted before the colon, so a phrase
+        let query = parser().parse("\"type:x\"");
+        assert_eq!(query.terms, ["type:x"]);
+        assert!(query.filters.is_empty());
+    }
+
+    #[test]
+    fn leading_dashes_exclude() {
+        let query = parser().parse("map -word -type:trait \"-kept\" -");
+        assert_eq!(query.terms, ["map", "-kept", "-"]);
+        assert_eq!(query.excluded, ["word"]);
+        assert_eq!(query.filters, [filter("type", "trait", true)]);
+
+        assert!(query.excludes("Some WORDS"));
+        assert!(!query.excludes("other"));
+    }
+
+    #[test]
+    fn keys_without_values_do_not_filter() {
+        let query = parser().parse("type: map");
+        assert!(query.filters.is_empty());
+        assert_eq!(query.terms, ["map"]);
+        assert!(query.allows("type", "anything"));
+    }
+
+    #[test]
+    fn allows_requested_values_but_not_negated_ones() {
+        let query = parser().parse("type:struct type:Trait");
+        assert!(query.allows("type", "struct"));
+        assert!(query.allows("type", "trait"));
+        assert!(!query.allows("type", "macro"));
+        assert!(query.allows("tag", "anything"));
+
+        let query = parser().parse("-type:macro");
+        assert!(query.allows("type", "struct"));
+        assert!(!query.allows("type", "MACRO"));
+    }
+
+    #[test]
+    fn completes_keys() {
+        let completions = parser().completions("map ty");
+        assert_eq!(completions.len(), 1);
+        assert_eq!(completions[0].name, "type:");
+        assert_eq!(completions[0].query, "map type:");
+
+        assert_eq!(names(parser().completions("-t")), ["-type:", "-tag:"]);
+    }
+
+    #[test]
+    fn completes_values_with_quotes_where_needed() {
+        let completions = parser().completions("type:t");
+        assert_eq!(
+            names(completions.clone()),
+            ["type:trait", "type:type alias"]
+        );
+        assert_eq!(completions[1].query, "type:\"type alias\" ");
+
+        assert_eq!(names(parser().completions("-type:S")), ["-type:struct"]);
+    }
+
+    #[test]
+    fn finished_and_quoted_words_are_not_completed() {
+        assert!(parser().completions("ty ").is_empty());
+        assert!(parser().completions("\"ty").is_empty());
+        assert!(parser().completions("type:struct").is_empty());
+        assert!(parser().completions("tag:").is_empty());
+        assert!(parser().completions("").is_empty());
+    }
+}
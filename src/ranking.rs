@@ -0,0 +1,168 @@
+//! Fuzzy ranking shared by the plugins, so every plugin orders its results the same way.
+
+use std::cmp::Reverse;
+
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+
+/// The text of an item that a query is matched against.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Fields<'a> {
+    pub name: &'a str,
+    pub description: &'a str,
+    pub keywords: &'a [String],
+    /// Added to the fuzzy score, e.g. a frecency boost from [`History`](crate::history::History).
+    pub boost: i64,
+}
+
+impl<'a> Fields<'a> {
+    pub fn new(name: &'a str) -> Self {
+        Self {
+            name,
+            ..Default::default()
+        }
+    }
+
+    pub fn description(mut self, description: &'a str) -> Self {
+        self.description = description;
+        self
+    }
+
+    pub fn keywords(mut self, keywords: &'a [String]) -> Self {
+        self.keywords = keywords;
+        self
+    }
+
+    pub fn boost(mut self, boost: i64) -> Self {
+        self.boost = boost;
+        self
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Score {
+    fuzzy: i64,
+    prefix: bool,
+    word_boundary: bool,
+    shorter_name: Reverse<usize>,
+}
+
+/// Ranks every matching item; how many of them are shown is up to the `max_results` the
+/// [`Responder`](crate::Responder) enforces.
+#[derive(Default)]
+pub struct Ranker {
+    matcher: SkimMatcherV2,
+}
+
+impl Ranker {
+    /// Filters out the items that do not match the query and sorts the rest best match first.
+    ///
+    /// Items with the same fuzzy score are ordered by whether their name starts with the query,
+    /// whether the query starts at a word boundary in the name and finally by name length. An
+    /// empty query matches everything and only orders by boost.
+    pub fn rank<T, I, F>(&self, query: &str, items: I, fields: F) -> Vec<T>
+    where
+        I: IntoIterator<Item = T>,
+        F: for<'b> Fn(&'b T) -> Fields<'b>,
+    {
+        let query = query.trim();
+
+        let mut scored = items
+            .into_iter()
+            .filter_map(|item| {
+                let score = self.score(query, fields(&item))?;
+                Some((item, score))
+            })
+            .collect::<Vec<_>>();
+
+        // Stable, so equally good items stay in the order the plugin produced them
+        scored.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        scored.into_iter().map(|(item, _score)| item).collect()
+    }
+
+    fn score(&self, query: &str, fields: Fields) -> Option<Score> {
+        if query.is_empty() {
+            return Some(Score {
+                fuzzy: fields.boost,
+                prefix: false,
+                word_boundary: false,
+                shorter_name: Reverse(0),
+            });
+        }
+
+        let name = self.matcher.fuzzy_match(fields.name, query);
+        // Matches outside the name are worth less than a match of the name itself
+        let description = self
+            .matcher
+            .fuzzy_match(fields.description, query)
+            .map(|score| score / 2);
+        let keywords = fields
+            .keywords
+            .iter()
+            .filter_map(|keyword| self.matcher.fuzzy_match(keyword, query))
+            .max()
+            .map(|score| score * 3 / 4);
+
+        let fuzzy = [name, description, keywords].into_iter().flatten().max()?;
+
+        let name_lower = fields.name.to_lowercase();
+        let query_lower = query.to_lowercase();
+
+        Some(Score {
+            fuzzy: fuzzy + fields.boost,
+            prefix: name_lower.starts_with(&query_lower),
+            word_boundary: name_lower
+                .split(|c: char| !c.is_alphanumeric())
+                .any(|word| word.starts_with(&query_lower)),
+            shorter_name: Reverse(fields.name.len()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rank<'a>(query: &str, names: &[&'a str]) -> Vec<&'a str> {
+        Ranker::default().rank(query, names.iter().copied(), |name| Fields::new(name))
+    }
+
+    #[test]
+    fn drops_items_that_do_not_match() {
+        assert_eq!(rank("fox", &["Firefox", "Files", "Terminal"]), ["Firefox"]);
+    }
+
+    #[test]
+    fn prefers_prefixes_then_word_boundaries_then_short_names() {
+        assert_eq!(
+            rank("term", &["xterm", "Terminal Preferences", "Terminal"]),
+            ["Terminal", "Terminal Preferences", "xterm"]
+        );
+        assert_eq!(rank("code", &["vscode", "VS Code"]), ["VS Code", "vscode"]);
+    }
+
+    #[test]
+    fn empty_queries_order_by_boost_only() {
+        let items = [("a", 0), ("b", 5), ("c", 0)];
+        let ranked =
+            Ranker::default().rank(" ", items, |(name, boost)| Fields::new(name).boost(*boost));
+        assert_eq!(ranked, [("b", 5), ("a", 0), ("c", 0)]);
+    }
+
+    #[test]
+    fn names_outweigh_descriptions_and_keywords() {
+        let keywords = ["browser".to_owned()];
+        let items = [
+            ("Web", "A browser", &[][..]),
+            ("Internet", "", &keywords[..]),
+            ("browser", "", &[][..]),
+        ];
+        let ranked = Ranker::default().rank("browser", items, |(name, description, keywords)| {
+            Fields::new(name)
+                .description(description)
+                .keywords(keywords)
+        });
+        let names: Vec<&str> = ranked.into_iter().map(|(name, _, _)| name).collect();
+        assert_eq!(names, ["browser", "Internet", "Web"]);
+    }
+}
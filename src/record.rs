@@ -0,0 +1,123 @@
+//! Recording the conversation between the launcher and a plugin, to replay it later.
+//!
+//! Set `record_file` in the plugin's config, or `POP_LAUNCHER_<PLUGIN>_RECORD_FILE`, and every
+//! request and response is appended to it as a JSON line. The `replay` binary feeds such a file
+//! back into the plugin and compares what it answers now with what was recorded.
+
+use std::{
+    cell::RefCell,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Instant,
+};
+
+use log::warn;
+use merge::Merge;
+use pop_launcher::{PluginResponse, Request};
+use serde::{Deserialize, Serialize};
+
+use crate::load_config;
+
+#[derive(Deserialize, Merge, Default)]
+struct RecordConfig {
+    record_file: Option<PathBuf>,
+}
+
+/// One line of a recording.
+#[derive(Debug, Deserialize)]
+pub struct Entry {
+    /// Milliseconds since the plugin started.
+    pub time: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+#[derive(Debug, Deserialize)]
+pub enum Event {
+    Request(Request),
+    Response(PluginResponse),
+}
+
+/// Appends entries to a recording. Clones write to the same file.
+#[derive(Clone)]
+pub struct Recorder {
+    file: Rc<RefCell<File>>,
+    start: Instant,
+}
+
+impl Recorder {
+    /// Opens the recording configured for the plugin, if any.
+    pub fn load(plugin_name: &str) -> Option<Self> {
+        let (config, _) = load_config::<RecordConfig>(plugin_name);
+        let path = config.record_file?;
+
+        Self::open(&path)
+            .map_err(|error| warn!("Could not open recording {path:?}: {error}"))
+            .ok()
+    }
+
+    /// Appends to the recording at `path`, creating it if needed.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Rc::new(RefCell::new(file)),
+            start: Instant::now(),
+        })
+    }
+
+    pub fn request(&self, request: &Request) {
+        self.record(EventRef::Request(request));
+    }
+
+    pub(crate) fn response(&self, response: &PluginResponse) {
+        self.record(EventRef::Response(response));
+    }
+
+    fn record(&self, event: EventRef) {
+        let entry = EntryRef {
+            time: self.start.elapsed().as_millis() as u64,
+            event,
+        };
+
+        let result = serde_json::to_string(&entry)
+            .map_err(io::Error::from)
+            .and_then(|line| writeln!(self.file.borrow_mut(), "{line}"));
+        if let Err(error) = result {
+            warn!("Could not record {:?}: {error}", entry.event);
+        }
+    }
+}
+
+// Writes the same JSON as `Entry`, without having to own requests and responses
+#[derive(Serialize)]
+struct EntryRef<'a> {
+    time: u64,
+    #[serde(flatten)]
+    event: EventRef<'a>,
+}
+
+#[derive(Debug, Serialize)]
+enum EventRef<'a> {
+    Request(&'a Request),
+    Response(&'a PluginResponse),
+}
+
+/// Reads a recording written by a [`Recorder`].
+pub fn read(path: &Path) -> io::Result<Vec<Entry>> {
+    let file = File::open(path)?;
+
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            serde_json::from_str(&line?).map_err(|error| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {error}", index + 1),
+                )
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,371 @@
+//! Drive plugins with scripted requests and collect what they answer, without a launcher.
+//!
+//! Only built for the crate's own tests and with the `testing` feature.
+
+#[cfg(feature = "notifications")]
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+#[cfg(feature = "notifications")]
+use dbus::{
+    arg::{RefArg, Variant},
+    blocking::Connection,
+    channel::{Channel, MatchingReceiver, Sender},
+    message::MatchRule,
+};
+use futures_lite::stream;
+use pop_launcher::{PluginResponse, Request};
+
+use crate::{cli::MemoryOutput, serve, PopLauncherPlugin};
+
+/// Replaces the plugin's responder with an in-memory one, runs the requests through the same
+/// loop `run_plugin` uses and returns every response the plugin produced, in order.
+pub async fn drive<P, I>(plugin: &mut P, requests: I) -> Vec<PluginResponse>
+where
+    P: PopLauncherPlugin,
+    I: IntoIterator<Item = Request>,
+{
+    let output = MemoryOutput::default();
+    plugin.responder().set_output(output.clone());
+
+    serve(plugin, stream::iter(requests.into_iter().map(Ok))).await;
+
+    output.take_responses()
+}
+
+/// The responses in short: the name of appended results, the variant of anything else.
+pub fn summary(responses: &[PluginResponse]) -> Vec<String> {
+    responses
+        .iter()
+        .map(|response| match response {
+            PluginResponse::Append(result) => result.name.clone(),
+            other => format!("{other:?}"),
+        })
+        .collect()
+}
+
+/// The id of the appended result called `name`.
+pub fn result_id(responses: &[PluginResponse], name: &str) -> u32 {
+    responses
+        .iter()
+        .find_map(|response| match response {
+            PluginResponse::Append(result) if result.name == name => Some(result.id),
+            _ => None,
+        })
+        .unwrap_or_else(|| panic!("No result {name} in {:?}", summary(responses)))
+}
+
+/// A directory below the system's temporary directory, removed again when dropped.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let name = format!(
+            "pop-launcher-plugins-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = std::env::temp_dir().join(name);
+        fs::create_dir_all(&path).expect("Could not create temporary directory");
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes a file at `relative` inside the directory, creating its parents.
+    pub fn write(&self, relative: impl AsRef<Path>, contents: &str) -> PathBuf {
+        let path = self.path.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("Could not create directory");
+        }
+        fs::write(&path, contents).expect("Could not write file");
+        path
+    }
+}
+
+impl Default for TempDir {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Held by tests while they change environment variables, which all their threads share.
+pub fn lock_env() -> MutexGuard<'static, ()> {
+    static ENV: Mutex<()> = Mutex::new(());
+    // A test failing while holding it does not leave the environment any less usable
+    ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// A home of its own: points the XDG base directories into a temporary directory, so config,
+/// caches and history of plugins under test start out empty and stay out of the user's.
+///
+/// Holds [`lock_env`] and restores the variables when dropped.
+pub struct XdgHome {
+    dir: TempDir,
+    saved: Vec<(&'static str, Option<OsString>)>,
+    _env: MutexGuard<'static, ()>,
+}
+
+impl XdgHome {
+    const VARIABLES: [(&'static str, &'static str); 6] = [
+        ("XDG_CONFIG_HOME", "config"),
+        ("XDG_CONFIG_DIRS", "config-dirs"),
+        ("XDG_DATA_HOME", "data"),
+        ("XDG_DATA_DIRS", "data-dirs"),
+        ("XDG_CACHE_HOME", "cache"),
+        ("XDG_STATE_HOME", "state"),
+    ];
+
+    pub fn new() -> Self {
+        let env = lock_env();
+        let dir = TempDir::new();
+
+        let mut saved: Vec<_> = Self::VARIABLES
+            .iter()
+            .map(|&(variable, subdir)| {
+                let path = dir.path().join(subdir);
+                fs::create_dir_all(&path).expect("Could not create directory");
+                let previous = env::var_os(variable);
+                env::set_var(variable, path);
+                (variable, previous)
+            })
+            .collect();
+        // Changed by `fake_program`
+        saved.push(("PATH", env::var_os("PATH")));
+
+        Self {
+            dir,
+            saved,
+            _env: env,
+        }
+    }
+
+    /// Writes a file at `relative` inside the home, e.g. `data/pop-launcher/plugins/...`.
+    pub fn write(&self, relative: impl AsRef<Path>, contents: &str) -> PathBuf {
+        self.dir.write(relative, contents)
+    }
+
+    /// Puts a script called `name` first on `PATH`, which writes the directory it runs in and
+    /// then its arguments, one per line, to the returned file.
+    pub fn fake_program(&self, name: &str) -> PathBuf {
+        let calls = self.dir.path().join("bin").join(format!("{name}.calls"));
+        self.script(
+            name,
+            &format!(
+                "pwd > '{0}'\nprintf '%s\\n' \"$@\" >> '{0}'",
+                calls.display()
+            ),
+        );
+        calls
+    }
+
+    /// Puts a script called `name` first on `PATH`, which prints `stdout` and `stderr` and fails.
+    pub fn failing_program(&self, name: &str, stdout: &str, stderr: &str) {
+        self.script(
+            name,
+            &format!("printf '{stdout}'\nprintf '{stderr}' >&2\nexit 1"),
+        );
+    }
+
+    fn script(&self, name: &str, body: &str) {
+        let bin = self.dir.path().join("bin");
+        let script = self.write(bin.join(name), &format!("#!/bin/sh\n{body}\n"));
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755))
+            .expect("Could not make script executable");
+
+        let path = env::var_os("PATH").unwrap_or_default();
+        if !env::split_paths(&path).any(|dir| dir == bin) {
+            let paths = std::iter::once(bin).chain(env::split_paths(&path));
+            env::set_var("PATH", env::join_paths(paths).expect("Invalid PATH"));
+        }
+    }
+}
+
+impl Default for XdgHome {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for XdgHome {
+    fn drop(&mut self) {
+        for (variable, previous) in self.saved.drain(..) {
+            match previous {
+                Some(value) => env::set_var(variable, value),
+                None => env::remove_var(variable),
+            }
+        }
+    }
+}
+
+/// A `dbus-daemon` of its own, killed when dropped.
+#[cfg(feature = "notifications")]
+pub struct PrivateBus {
+    address: String,
+    daemon: Child,
+    // Holds the configuration and the socket
+    _dir: TempDir,
+}
+
+#[cfg(feature = "notifications")]
+impl PrivateBus {
+    /// Starts a bus, `None` if there is no `dbus-daemon` to start it with.
+    pub fn start() -> Option<Self> {
+        let dir = TempDir::new();
+        let config = dir.write(
+            "bus.conf",
+            &format!(
+                "<busconfig>\
+                   <type>session</type>\
+                   <listen>unix:dir={}</listen>\
+                   <policy context=\"default\">\
+                     <allow send_destination=\"*\"/>\
+                     <allow receive_sender=\"*\"/>\
+                     <allow own=\"*\"/>\
+                   </policy>\
+                 </busconfig>",
+                dir.path().display()
+            ),
+        );
+
+        let mut daemon = Command::new("dbus-daemon")
+            .arg("--nofork")
+            .arg("--print-address")
+            .arg(format!("--config-file={}", config.display()))
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok()?;
+
+        let mut address = String::new();
+        let stdout = daemon.stdout.take()?;
+        BufReader::new(stdout).read_line(&mut address).ok()?;
+
+        Some(Self {
+            address: address.trim().to_owned(),
+            daemon,
+            _dir: dir,
+        })
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+}
+
+#[cfg(feature = "notifications")]
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// A notification received by a [`NotificationDaemon`].
+#[cfg(feature = "notifications")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub app_name: String,
+    pub icon: String,
+    pub summary: String,
+    pub body: String,
+}
+
+/// Stand-in for a notification daemon on a private bus, e.g. one started with
+/// `dbus-daemon --session --print-address`.
+///
+/// Point `DBUS_SESSION_BUS_ADDRESS` at the same bus and everything sent through
+/// [`crate::notify`] ends up in [`NotificationDaemon::received`].
+#[cfg(feature = "notifications")]
+pub struct NotificationDaemon {
+    received: Arc<Mutex<Vec<Notification>>>,
+}
+
+#[cfg(feature = "notifications")]
+impl NotificationDaemon {
+    /// Claims `org.freedesktop.Notifications` on the bus at `address` and answers on a
+    /// background thread for as long as the process lives.
+    pub fn start(address: &str) -> Result<Self, dbus::Error> {
+        let mut channel = Channel::open_private(address)?;
+        channel.register()?;
+        let connection = Connection::from(channel);
+        connection.request_name("org.freedesktop.Notifications", false, true, true)?;
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        let mut next_id = 0u32;
+        connection.start_receive(
+            MatchRule::new_method_call().with_interface("org.freedesktop.Notifications"),
+            Box::new(move |message, connection| {
+                type Notify = (
+                    String,
+                    u32,
+                    String,
+                    String,
+                    String,
+                    Vec<String>,
+                    HashMap<String, Variant<Box<dyn RefArg>>>,
+                    i32,
+                );
+
+                let reply = match message.read_all::<Notify>() {
+                    Ok((app_name, _, icon, summary, body, ..)) => {
+                        sink.lock().unwrap().push(Notification {
+                            app_name,
+                            icon,
+                            summary,
+                            body,
+                        });
+                        next_id += 1;
+                        message.method_return().append1(next_id)
+                    }
+                    // GetCapabilities and friends, which the notify module does not use
+                    Err(_) => message.method_return(),
+                };
+                let _ = connection.send(reply);
+                true
+            }),
+        );
+
+        thread::spawn(move || loop {
+            if connection.process(Duration::from_secs(1)).is_err() {
+                break;
+            }
+        });
+
+        Ok(Self { received })
+    }
+
+    /// Every notification received so far, oldest first.
+    pub fn received(&self) -> Vec<Notification> {
+        self.received.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,125 @@
+//! Notices changes to config files and directories, so plugins can reload without a restart.
+
+use std::{
+    fs::{metadata, read_dir},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Modification times of a file, or of every entry of a directory. Empty if the path is missing.
+pub(crate) type Fingerprint = Vec<(PathBuf, Option<SystemTime>)>;
+
+/// Polls a fixed set of paths for changes.
+///
+/// Paths that do not exist yet are watched too, so creating a config file counts as a change.
+/// Directories are watched one level deep: adding, removing or editing a file inside is noticed.
+#[derive(Debug, Default)]
+pub struct Watcher {
+    paths: Vec<PathBuf>,
+    snapshot: Vec<Fingerprint>,
+}
+
+impl Watcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let snapshot = paths.iter().map(|path| fingerprint(path)).collect();
+        Self { paths, snapshot }
+    }
+
+    /// Whether any of the paths changed since the last call (or since creating the watcher).
+    pub fn changed(&mut self) -> bool {
+        let snapshot = self
+            .paths
+            .iter()
+            .map(|path| fingerprint(path))
+            .collect::<Vec<_>>();
+
+        if snapshot == self.snapshot {
+            return false;
+        }
+
+        self.snapshot = snapshot;
+        true
+    }
+}
+
+pub(crate) fn fingerprint(path: &Path) -> Fingerprint {
+    let modified = |path: &Path| metadata(path).and_then(|meta| meta.modified()).ok();
+
+    if path.is_dir() {
+        let mut entries = match read_dir(path) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| {
+                    let path = entry.path();
+                    let time = modified(&path);
+                    (path, time)
+                })
+                .collect::<Vec<_>>(),
+            Err(_) => Vec::new(),
+        };
+        entries.sort();
+        entries
+    } else if path.exists() {
+        vec![(path.to_owned(), modified(path))]
+    } else {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{remove_file, File},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::testing::TempDir;
+
+    // Writes in quick succession can leave the modification time as it was
+    fn age(path: &Path, seconds: u64) {
+        let time = SystemTime::now() - Duration::from_secs(seconds);
+        File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(time))
+            .unwrap();
+    }
+
+    #[test]
+    fn notices_files_being_created_changed_and_removed() {
+        let dir = TempDir::new();
+        let config = dir.path().join("config.toml");
+        let mut watcher = Watcher::new(vec![config.clone()]);
+        assert!(!watcher.changed());
+
+        dir.write("config.toml", "prefix = \"a\"");
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+
+        age(&config, 60);
+        assert!(watcher.changed());
+
+        remove_file(&config).unwrap();
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+    }
+
+    #[test]
+    fn watches_directories_one_level_deep() {
+        let dir = TempDir::new();
+        let first = dir.write("commandos/first.toml", "");
+        let mut watcher = Watcher::new(vec![dir.path().join("commandos")]);
+
+        dir.write("commandos/second.toml", "");
+        assert!(watcher.changed());
+
+        age(&first, 60);
+        assert!(watcher.changed());
+
+        let nested = dir.write("commandos/nested/third.toml", "");
+        assert!(watcher.changed());
+        age(&nested, 60);
+        assert!(!watcher.changed());
+    }
+}